@@ -1,4 +1,5 @@
-use clap::{Arg, ArgMatches, Command};
+use crate::recheck_build;
+use clap::{Arg, ArgGroup, ArgMatches, Command};
 
 /// A struct for parsing command-line arguments.
 pub struct ArgParser {
@@ -17,7 +18,7 @@ impl ArgBuilder {
             .long("input")
             .help("Input compile_commands.json file generated from vgbuild")
             .action(clap::ArgAction::Set)
-            .required(true)
+            .required_unless_present("export_clangd")
     }
 
     /// Creates the argument for the append file.
@@ -31,6 +32,36 @@ impl ArgBuilder {
             .required(false)
     }
 
+    /// Creates the argument for the append mode.
+    pub fn append_mode_arg() -> Arg {
+        Arg::new("append_mode")
+            .long("append-mode")
+            .help("How to combine the append files: concatenate their entries, or overlay them so that they replace the entries of the same files")
+            .action(clap::ArgAction::Set)
+            .value_parser(["concat", "overlay"])
+            .required(false)
+            .default_value("concat")
+    }
+
+    /// Creates the argument for the list of files whose entries are removed.
+    pub fn remove_entries_arg() -> Arg {
+        Arg::new("remove_entries")
+            .long("remove-entries")
+            .value_name("file")
+            .help("Text file listing source files (one per line) whose entries are removed")
+            .action(clap::ArgAction::Set)
+            .required(false)
+    }
+
+    /// Creates the argument for expanding response files inline.
+    pub fn expand_rsp_arg() -> Arg {
+        Arg::new("expand_rsp")
+            .long("expand-rsp")
+            .help("Replace every @file argument naming an existing response file by its arguments")
+            .required(false)
+            .action(clap::ArgAction::SetTrue)
+    }
+
     /// Creates the argument for the postprocess config.
     pub fn postprocess_config_arg() -> Arg {
         Arg::new("postprocess_config")
@@ -42,17 +73,39 @@ impl ArgBuilder {
             .required(false)
     }
 
+    /// Creates the argument for the postprocess config profile.
+    pub fn profile_arg() -> Arg {
+        Arg::new("profile")
+            .long("profile")
+            .value_name("profile")
+            .help("Named profile of the postprocess config to apply on top of its base rules")
+            .action(clap::ArgAction::Set)
+            .requires("postprocess_config")
+            .required(false)
+    }
+
     /// Creates the argument for the keep duplicated file option.
     pub fn keep_duplicated_file_arg() -> Arg {
         Arg::new("keep_duplicated_file")
             .long("keep-duplicated")
-            .help("How to handle duplicate files: keep all, retain first occurrence, or retain last occurrence")
+            .help("How to handle duplicate files: keep all, retain first occurrence, retain last occurrence, or merge their include directories and defines")
             .action(clap::ArgAction::Set)
-            .value_parser(["keep", "retain_first", "retain_last"])
+            .value_parser(["keep", "retain_first", "retain_last", "merge"])
             .required(false)
             .default_value("retain_first")
     }
 
+    /// Creates the argument for the attributes identifying duplicate files.
+    pub fn dedup_key_arg() -> Arg {
+        Arg::new("dedup_key")
+            .long("dedup-key")
+            .help("Attributes identifying duplicate files: the source file, or the source file and its output to keep build variants apart")
+            .action(clap::ArgAction::Set)
+            .value_parser(["file", "file_output"])
+            .required(false)
+            .default_value("file")
+    }
+
     /// Creates the argument for skipping non-existed files.
     pub fn skip_nonexisted_file_arg() -> Arg {
         Arg::new("skip_nonexisted_file")
@@ -80,6 +133,188 @@ impl ArgBuilder {
             .action(clap::ArgAction::Set)
     }
 
+    /// Creates the argument for explaining where the entries of a file came from.
+    pub fn explain_arg() -> Arg {
+        Arg::new("explain")
+            .long("explain")
+            .value_name("file")
+            .help("Show every entry of the file across all inputs with its source database and entry index, and whether it was kept")
+            .required(false)
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for reporting duplicate files.
+    pub fn report_duplicates_arg() -> Arg {
+        Arg::new("report_duplicates")
+            .long("report-duplicates")
+            .value_name("format")
+            .help("List every file with more than one entry across all inputs, with the source of each entry and its -I/-D/-O/-std differences")
+            .required(false)
+            .num_args(0..=1)
+            .default_missing_value("text")
+            .value_parser(["text", "json"])
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for writing the source database of each entry.
+    pub fn emit_source_arg() -> Arg {
+        Arg::new("emit_source")
+            .long("emit-source")
+            .help("Write the source database and entry index of each entry as an extra \"source\" field")
+            .required(false)
+            .action(clap::ArgAction::SetTrue)
+    }
+
+    /// Creates the argument for factoring shared flags into response files.
+    pub fn factor_rsp_arg() -> Arg {
+        Arg::new("factor_rsp")
+            .long("factor-rsp")
+            .value_name("dir")
+            .help("Move the include and define blocks shared by several entries into response files in this directory, referenced as @file")
+            .required(false)
+            .conflicts_with("output_mode")
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for the fewest entries sharing a factored block.
+    pub fn rsp_min_users_arg() -> Arg {
+        Arg::new("rsp_min_users")
+            .long("rsp-min-users")
+            .value_name("count")
+            .help("Only factor blocks shared by at least this many entries")
+            .required(false)
+            .requires("factor_rsp")
+            .value_parser(clap::value_parser!(usize))
+            .action(clap::ArgAction::Set)
+            .default_value("2")
+    }
+
+    /// Creates the argument for synthesizing header entries.
+    pub fn synthesize_headers_arg() -> Arg {
+        Arg::new("synthesize_headers")
+            .long("synthesize-headers")
+            .help("Add an entry for every header in the -I directories, with the flags of the best-matching translation unit")
+            .required(false)
+            .action(clap::ArgAction::SetTrue)
+    }
+
+    /// Creates the argument for sorting the output.
+    pub fn sort_arg() -> Arg {
+        Arg::new("sort")
+            .long("sort")
+            .help("Sort the output by the file path, directory or output of each entry")
+            .action(clap::ArgAction::Set)
+            .value_parser(["file", "directory", "output"])
+            .required(false)
+    }
+
+    /// Creates the argument for the layout of the output.
+    pub fn format_arg() -> Arg {
+        Arg::new("format")
+            .long("format")
+            .help("Layout of the output: an indented object or a single line per entry")
+            .action(clap::ArgAction::Set)
+            .value_parser(["pretty", "compact"])
+            .required(false)
+            .default_value("pretty")
+    }
+
+    /// Creates the argument for the per-translation-unit report.
+    pub fn report_units_arg() -> Arg {
+        Arg::new("report_units")
+            .long("report-units")
+            .value_name("format")
+            .help("Write one row per translation unit with its compiler, standard, optimization level, include and define counts, existence and source database")
+            .required(false)
+            .num_args(0..=1)
+            .default_missing_value("csv")
+            .value_parser(["csv", "tsv"])
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for generating compile_flags.txt files.
+    pub fn emit_compile_flags_arg() -> Arg {
+        Arg::new("emit_compile_flags")
+            .long("emit-compile-flags")
+            .help("Write a compile_flags.txt with the flags shared by the translation units of each source directory")
+            .required(false)
+            .action(clap::ArgAction::SetTrue)
+    }
+
+    /// Creates the argument for the roots of the generated compile_flags.txt files.
+    pub fn compile_flags_roots_arg() -> Arg {
+        Arg::new("compile_flags_roots")
+            .long("compile-flags-roots")
+            .value_name("dirs")
+            .help("Only write compile_flags.txt files in these directories and below (comma-separated)")
+            .required(false)
+            .requires("emit_compile_flags")
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for generating a Ninja file rechecking the translation units.
+    pub fn emit_ninja_arg() -> Arg {
+        Arg::new("emit_ninja")
+            .long("emit-ninja")
+            .value_name("file")
+            .help("Write a build.ninja that rechecks every translation unit with the check template")
+            .required(false)
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for generating a shell script rechecking the translation units.
+    pub fn emit_script_arg() -> Arg {
+        Arg::new("emit_script")
+            .long("emit-script")
+            .value_name("file")
+            .help("Write a shell script that rechecks every translation unit with the check template")
+            .required(false)
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for the command template of the recheck.
+    pub fn check_template_arg() -> Arg {
+        Arg::new("check_template")
+            .long("check-template")
+            .value_name("template")
+            .help("Command of each recheck; {command}, {file} and {directory} are replaced")
+            .required(false)
+            .default_value(recheck_build::DEFAULT_CHECK_TEMPLATE)
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for exporting VS Code C/C++ configurations.
+    pub fn export_vscode_arg() -> Arg {
+        Arg::new("export_vscode")
+            .long("export-vscode")
+            .value_name("file")
+            .help("Write a c_cpp_properties.json with one configuration per set of include directories and defines")
+            .required(false)
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for exporting the postprocess config as a .clangd file.
+    pub fn export_clangd_arg() -> Arg {
+        Arg::new("export_clangd")
+            .long("export-clangd")
+            .value_name("file")
+            .help("Write the remove/insert rules of the postprocess config as a .clangd CompileFlags file; needs no --input")
+            .required(false)
+            .requires("postprocess_config")
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for splitting the output into per-subproject databases.
+    pub fn split_by_arg() -> Arg {
+        Arg::new("split_by")
+            .long("split-by")
+            .value_name("dir-prefixes")
+            .help("Write a compile_commands.json under each of these subproject roots (comma-separated, a trailing /* makes each child directory a root); other entries go to stdout")
+            .required(false)
+            .conflicts_with("output_mode")
+            .action(clap::ArgAction::Set)
+    }
+
     /// Creates the argument for interactive file selection.
     pub fn select_file_arg() -> Arg {
         Arg::new("select_file")
@@ -89,6 +324,78 @@ impl ArgBuilder {
             .action(clap::ArgAction::SetTrue)
             .required(false)
     }
+
+    /// Creates the argument for the extensions offered by the file selection.
+    pub fn select_extensions_arg() -> Arg {
+        Arg::new("select_extensions")
+            .long("select-extensions")
+            .value_name("exts")
+            .help("Extensions of the files offered by --select_file, without the dot (comma-separated) [default: cpp,cxx,cc,c++,C]")
+            .required(false)
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for the languages offered by the file selection.
+    pub fn select_languages_arg() -> Arg {
+        Arg::new("select_languages")
+            .long("select-languages")
+            .value_name("langs")
+            .help("Offer the files compiled as these languages instead, detected from -x, the compiler driver and the extension (comma-separated, e.g. c,c++,cuda)")
+            .required(false)
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for matching the files without the interactive picker.
+    pub fn select_query_arg() -> Arg {
+        Arg::new("select_query")
+            .long("select-query")
+            .value_name("q")
+            .help("Print the files --select_file would offer that match this query, best match first, without a terminal")
+            .required(false)
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for the most files printed by the query.
+    pub fn select_limit_arg() -> Arg {
+        Arg::new("select_limit")
+            .long("select-limit")
+            .value_name("N")
+            .help("Print at most this many files matching --select-query")
+            .required(false)
+            .requires("select_query")
+            .value_parser(clap::value_parser!(usize))
+            .action(clap::ArgAction::Set)
+    }
+
+    /// Creates the argument for turning off the selection history.
+    pub fn no_history_arg() -> Arg {
+        Arg::new("no_history")
+            .long("no-history")
+            .help("Neither rank the files offered by --select_file by past selections nor record the selection")
+            .required(false)
+            .action(clap::ArgAction::SetTrue)
+    }
+
+    /// Creates the argument for the interactive flag editor.
+    pub fn edit_flags_arg() -> Arg {
+        Arg::new("edit_flags")
+            .long("edit-flags")
+            .help("Pick a file, mark flags to remove and type flags to insert, and save them into the postprocess config, globally or for the file's directory")
+            .required(false)
+            .requires("postprocess_config")
+            .action(clap::ArgAction::SetTrue)
+    }
+
+    /// Creates the argument for what the file selection outputs.
+    pub fn select_output_arg() -> Arg {
+        Arg::new("select_output")
+            .long("select-output")
+            .help("What to print for the selected files: their paths, their directory and command, or a compile database of their entries")
+            .action(clap::ArgAction::Set)
+            .value_parser(["paths", "commands", "ccj"])
+            .required(false)
+            .default_value("paths")
+    }
 }
 
 impl ArgParser {
@@ -100,8 +407,8 @@ impl ArgParser {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::arg_parser::ArgParser;
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
     /// let arg_parser = ArgParser::parse();
     /// ```
     pub fn parse() -> Self {
@@ -117,12 +424,58 @@ impl ArgParser {
             .about("compile_commands.json postprocess for zebu")
             .arg(ArgBuilder::input_file_arg())
             .arg(ArgBuilder::append_file_arg())
+            .arg(ArgBuilder::append_mode_arg())
+            .arg(ArgBuilder::remove_entries_arg())
+            .arg(ArgBuilder::expand_rsp_arg())
             .arg(ArgBuilder::postprocess_config_arg())
+            .arg(ArgBuilder::profile_arg())
             .arg(ArgBuilder::keep_duplicated_file_arg())
+            .arg(ArgBuilder::dedup_key_arg())
             .arg(ArgBuilder::skip_nonexisted_file_arg())
             .arg(ArgBuilder::dump_transunit_list_arg())
             .arg(ArgBuilder::find_command_arg())
+            .arg(ArgBuilder::explain_arg())
+            .arg(ArgBuilder::report_duplicates_arg())
+            .arg(ArgBuilder::emit_source_arg())
+            .arg(ArgBuilder::factor_rsp_arg())
+            .arg(ArgBuilder::rsp_min_users_arg())
+            .arg(ArgBuilder::synthesize_headers_arg())
+            .arg(ArgBuilder::sort_arg())
+            .arg(ArgBuilder::format_arg())
+            .arg(ArgBuilder::report_units_arg())
+            .arg(ArgBuilder::emit_compile_flags_arg())
+            .arg(ArgBuilder::compile_flags_roots_arg())
+            .arg(ArgBuilder::emit_ninja_arg())
+            .arg(ArgBuilder::emit_script_arg())
+            .arg(ArgBuilder::check_template_arg())
+            .arg(ArgBuilder::export_vscode_arg())
+            .arg(ArgBuilder::export_clangd_arg())
+            .arg(ArgBuilder::split_by_arg())
             .arg(ArgBuilder::select_file_arg())
+            .arg(ArgBuilder::select_output_arg())
+            .arg(ArgBuilder::edit_flags_arg())
+            .arg(ArgBuilder::select_extensions_arg())
+            .arg(ArgBuilder::select_languages_arg())
+            .arg(ArgBuilder::no_history_arg())
+            .arg(ArgBuilder::select_query_arg())
+            .arg(ArgBuilder::select_limit_arg())
+            // each of these writes its own output and returns, so only one may be given;
+            // --factor-rsp and --split-by shape the printed database and conflict with them
+            .group(ArgGroup::new("output_mode").multiple(false).args([
+                "dump_TransUnit_list",
+                "FindCommand",
+                "explain",
+                "report_duplicates",
+                "report_units",
+                "emit_compile_flags",
+                "emit_ninja",
+                "emit_script",
+                "export_vscode",
+                "export_clangd",
+                "select_file",
+                "select_query",
+                "edit_flags",
+            ]))
     }
 
     /// Returns the input file path.
@@ -133,8 +486,8 @@ impl ArgParser {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::arg_parser::ArgParser;
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
     /// let arg_parser = ArgParser::parse();
     /// let input_file = arg_parser.get_input_file();
     /// ```
@@ -150,8 +503,8 @@ impl ArgParser {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::arg_parser::ArgParser;
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
     /// let arg_parser = ArgParser::parse();
     /// let config = arg_parser.get_postprocess_config();
     /// ```
@@ -159,6 +512,23 @@ impl ArgParser {
         self.matches.get_one::<String>("postprocess_config")
     }
 
+    /// Returns the profile of the postprocess config.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The profile name if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let profile = arg_parser.get_profile();
+    /// ```
+    pub fn get_profile(&self) -> Option<&String> {
+        self.matches.get_one::<String>("profile")
+    }
+
     /// Returns the append file paths.
     ///
     /// # Returns
@@ -167,8 +537,8 @@ impl ArgParser {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::arg_parser::ArgParser;
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
     /// let arg_parser = ArgParser::parse();
     /// let append_files = arg_parser.get_append_files();
     /// ```
@@ -176,6 +546,40 @@ impl ArgParser {
         self.matches.get_one::<String>("append_file")
     }
 
+    /// Returns the append mode.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The append mode if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let append_mode = arg_parser.get_append_mode();
+    /// ```
+    pub fn get_append_mode(&self) -> Option<&String> {
+        self.matches.get_one::<String>("append_mode")
+    }
+
+    /// Returns the path of the list of files whose entries are removed.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The remove list path if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let remove_list = arg_parser.get_remove_entries();
+    /// ```
+    pub fn get_remove_entries(&self) -> Option<&String> {
+        self.matches.get_one::<String>("remove_entries")
+    }
+
     /// Returns the keep duplicated option.
     ///
     /// # Returns
@@ -184,8 +588,8 @@ impl ArgParser {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::arg_parser::ArgParser;
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
     /// let arg_parser = ArgParser::parse();
     /// let keep_duplicated = arg_parser.get_keep_duplicated();
     /// ```
@@ -193,6 +597,23 @@ impl ArgParser {
         self.matches.get_one::<String>("keep_duplicated_file")
     }
 
+    /// Returns the attributes identifying duplicate files.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The dedup key if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let dedup_key = arg_parser.get_dedup_key();
+    /// ```
+    pub fn get_dedup_key(&self) -> Option<&String> {
+        self.matches.get_one::<String>("dedup_key")
+    }
+
     /// Returns whether to dump the transunit list.
     ///
     /// # Returns
@@ -201,15 +622,15 @@ impl ArgParser {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::arg_parser::ArgParser;
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
     /// let arg_parser = ArgParser::parse();
     /// let is_dump = arg_parser.is_dump_transunit_list();
     /// ```
     pub fn is_dump_transunit_list(&self) -> bool {
         self.matches
             .get_one::<bool>("dump_TransUnit_list")
-            .map(|x| *x)
+            .copied()
             .unwrap_or(false)
     }
 
@@ -221,8 +642,8 @@ impl ArgParser {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::arg_parser::ArgParser;
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
     /// let arg_parser = ArgParser::parse();
     /// let find_command = arg_parser.find_the_command();
     /// ```
@@ -230,6 +651,60 @@ impl ArgParser {
         self.matches.get_one::<String>("FindCommand")
     }
 
+    /// Returns the file to explain.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The file to explain if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let explain = arg_parser.get_explain_file();
+    /// ```
+    pub fn get_explain_file(&self) -> Option<&String> {
+        self.matches.get_one::<String>("explain")
+    }
+
+    /// Returns the format of the duplicate report.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The report format if the report is requested, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let report_duplicates = arg_parser.get_report_duplicates();
+    /// ```
+    pub fn get_report_duplicates(&self) -> Option<&String> {
+        self.matches.get_one::<String>("report_duplicates")
+    }
+
+    /// Returns whether to write the source database of each entry.
+    ///
+    /// # Returns
+    ///
+    /// - `bool` - `true` if the source should be written, otherwise `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let emit_source = arg_parser.is_emit_source();
+    /// ```
+    pub fn is_emit_source(&self) -> bool {
+        self.matches
+            .get_one::<bool>("emit_source")
+            .copied()
+            .unwrap_or(false)
+    }
+
     /// Returns whether to skip non-existed files.
     ///
     /// # Returns
@@ -238,18 +713,282 @@ impl ArgParser {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::arg_parser::ArgParser;
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
     /// let arg_parser = ArgParser::parse();
     /// let skip = arg_parser.skip_nonexisted_file();
     /// ```
     pub fn skip_nonexisted_file(&self) -> bool {
         self.matches
             .get_one::<bool>("skip_nonexisted_file")
-            .map(|x| *x)
+            .copied()
             .unwrap_or(false)
     }
 
+    /// Returns whether to expand response files inline.
+    ///
+    /// # Returns
+    ///
+    /// - `bool` - `true` if response files should be expanded, otherwise `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let expand_rsp = arg_parser.is_expand_rsp();
+    /// ```
+    pub fn is_expand_rsp(&self) -> bool {
+        self.matches
+            .get_one::<bool>("expand_rsp")
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Returns the directory of the factored response files.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The response file directory if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let factor_rsp = arg_parser.get_factor_rsp();
+    /// ```
+    pub fn get_factor_rsp(&self) -> Option<&String> {
+        self.matches.get_one::<String>("factor_rsp")
+    }
+
+    /// Returns the fewest entries sharing a factored block.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&usize>` - The count if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let rsp_min_users = arg_parser.get_rsp_min_users();
+    /// ```
+    pub fn get_rsp_min_users(&self) -> Option<&usize> {
+        self.matches.get_one::<usize>("rsp_min_users")
+    }
+
+    /// Returns whether to synthesize header entries.
+    ///
+    /// # Returns
+    ///
+    /// - `bool` - `true` if header entries should be added, otherwise `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let synthesize_headers = arg_parser.is_synthesize_headers();
+    /// ```
+    pub fn is_synthesize_headers(&self) -> bool {
+        self.matches
+            .get_one::<bool>("synthesize_headers")
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Returns the attribute the output is sorted by.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The sort key if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let sort = arg_parser.get_sort();
+    /// ```
+    pub fn get_sort(&self) -> Option<&String> {
+        self.matches.get_one::<String>("sort")
+    }
+
+    /// Returns the layout of the output.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The output format if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let format = arg_parser.get_format();
+    /// ```
+    pub fn get_format(&self) -> Option<&String> {
+        self.matches.get_one::<String>("format")
+    }
+
+    /// Returns the format of the per-translation-unit report.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The report format if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let report_units = arg_parser.get_report_units();
+    /// ```
+    pub fn get_report_units(&self) -> Option<&String> {
+        self.matches.get_one::<String>("report_units")
+    }
+
+    /// Returns whether to generate compile_flags.txt files.
+    ///
+    /// # Returns
+    ///
+    /// - `bool` - `true` if compile_flags.txt files should be generated, otherwise `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let emit_compile_flags = arg_parser.is_emit_compile_flags();
+    /// ```
+    pub fn is_emit_compile_flags(&self) -> bool {
+        self.matches
+            .get_one::<bool>("emit_compile_flags")
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Returns the roots of the generated compile_flags.txt files.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The comma-separated roots if they exist, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let roots = arg_parser.get_compile_flags_roots();
+    /// ```
+    pub fn get_compile_flags_roots(&self) -> Option<&String> {
+        self.matches.get_one::<String>("compile_flags_roots")
+    }
+
+    /// Returns the path of the generated Ninja file.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The Ninja file path if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let emit_ninja = arg_parser.get_emit_ninja();
+    /// ```
+    pub fn get_emit_ninja(&self) -> Option<&String> {
+        self.matches.get_one::<String>("emit_ninja")
+    }
+
+    /// Returns the path of the generated shell script.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The shell script path if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let emit_script = arg_parser.get_emit_script();
+    /// ```
+    pub fn get_emit_script(&self) -> Option<&String> {
+        self.matches.get_one::<String>("emit_script")
+    }
+
+    /// Returns the command template of the recheck.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The command template.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let check_template = arg_parser.get_check_template();
+    /// ```
+    pub fn get_check_template(&self) -> Option<&String> {
+        self.matches.get_one::<String>("check_template")
+    }
+
+    /// Returns the path of the exported c_cpp_properties.json file.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The c_cpp_properties.json path if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let export_vscode = arg_parser.get_export_vscode();
+    /// ```
+    pub fn get_export_vscode(&self) -> Option<&String> {
+        self.matches.get_one::<String>("export_vscode")
+    }
+
+    /// Returns the path of the exported .clangd file.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The .clangd file path if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let export_clangd = arg_parser.get_export_clangd();
+    /// ```
+    pub fn get_export_clangd(&self) -> Option<&String> {
+        self.matches.get_one::<String>("export_clangd")
+    }
+
+    /// Returns the subproject root rules of the split output.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The comma-separated directory prefix rules if they exist, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let split_by = arg_parser.get_split_by();
+    /// ```
+    pub fn get_split_by(&self) -> Option<&String> {
+        self.matches.get_one::<String>("split_by")
+    }
+
     /// Returns whether to use interactive file selection.
     ///
     /// # Returns
@@ -258,15 +997,172 @@ impl ArgParser {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::arg_parser::ArgParser;
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
     /// let arg_parser = ArgParser::parse();
     /// let select_file = arg_parser.is_select_file();
     /// ```
     pub fn is_select_file(&self) -> bool {
         self.matches
             .get_one::<bool>("select_file")
-            .map(|x| *x)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Returns the extensions offered by the file selection.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The comma-separated extensions if they exist, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let select_extensions = arg_parser.get_select_extensions();
+    /// ```
+    pub fn get_select_extensions(&self) -> Option<&String> {
+        self.matches.get_one::<String>("select_extensions")
+    }
+
+    /// Returns the languages offered by the file selection.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The comma-separated languages if they exist, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let select_languages = arg_parser.get_select_languages();
+    /// ```
+    pub fn get_select_languages(&self) -> Option<&String> {
+        self.matches.get_one::<String>("select_languages")
+    }
+
+    /// Returns the query matching the files without the interactive picker.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The query if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let select_query = arg_parser.get_select_query();
+    /// ```
+    pub fn get_select_query(&self) -> Option<&String> {
+        self.matches.get_one::<String>("select_query")
+    }
+
+    /// Returns the most files printed by the query.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&usize>` - The limit if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let select_limit = arg_parser.get_select_limit();
+    /// ```
+    pub fn get_select_limit(&self) -> Option<&usize> {
+        self.matches.get_one::<usize>("select_limit")
+    }
+
+    /// Returns whether the selection history is turned off.
+    ///
+    /// # Returns
+    ///
+    /// - `bool` - `true` if the selection history should not be used, otherwise `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let no_history = arg_parser.is_no_history();
+    /// ```
+    pub fn is_no_history(&self) -> bool {
+        self.matches
+            .get_one::<bool>("no_history")
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Returns whether to launch the interactive flag editor.
+    ///
+    /// # Returns
+    ///
+    /// - `bool` - `true` if the flag editor should be launched, otherwise `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let edit_flags = arg_parser.is_edit_flags();
+    /// ```
+    pub fn is_edit_flags(&self) -> bool {
+        self.matches
+            .get_one::<bool>("edit_flags")
+            .copied()
             .unwrap_or(false)
     }
+
+    /// Returns what the file selection outputs.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<&String>` - The selection output if it exists, otherwise `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::arg_parser::ArgParser;
+    /// let arg_parser = ArgParser::parse();
+    /// let select_output = arg_parser.get_select_output();
+    /// ```
+    pub fn get_select_output(&self) -> Option<&String> {
+        self.matches.get_one::<String>("select_output")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_modes_are_mutually_exclusive() {
+        let command = || ArgParser::build_command();
+        let ninja = ["ccj_postprocess", "-i", "a.json", "--emit-ninja", "b"];
+        assert!(command().try_get_matches_from(ninja).is_ok());
+        let split = ninja.iter().chain(&["--split-by", "c"]);
+        let error = command().try_get_matches_from(split).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+        let report = ninja.iter().chain(&["--report-units", "csv"]);
+        let error = command().try_get_matches_from(report).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn factor_rsp_combines_with_split_by() {
+        let factor_split = [
+            "ccj_postprocess",
+            "-i",
+            "a.json",
+            "--factor-rsp",
+            "rsp",
+            "--split-by",
+            "c",
+        ];
+        assert!(ArgParser::build_command().try_get_matches_from(factor_split).is_ok());
+    }
 }
@@ -0,0 +1,238 @@
+use crate::postprocess_config::{PathRule, PostProcessConfig};
+
+/// Characters with a special meaning in a regex.
+const REGEX_META_CHARACTERS: &str = ".^$*+?()[]{}|\\";
+
+/// Translates a postprocess config into a `.clangd` file.
+///
+/// The global `remove` and `insert` rules become the `CompileFlags: Remove/Add` of the first
+/// fragment, and every path rule becomes a fragment guarded by `If: PathMatch`. clangd
+/// matches `PathMatch` against the path relative to the directory of the `.clangd` file, so
+/// absolute path regexes under `base_directory` are made relative to it.
+///
+/// # Arguments
+///
+/// * `ppc` - The postprocess config.
+/// * `base_directory` - The directory the `.clangd` file is written to.
+///
+/// # Returns
+///
+/// - `(String, Vec<String>)` - The YAML text and a warning for every rule that cannot be expressed.
+///
+/// # Examples
+///
+/// ```
+/// use ccj_postprocess::clangd_config;
+/// use ccj_postprocess::postprocess_config::{PathRule, PostProcessConfig};
+///
+/// let ppc = PostProcessConfig {
+///     remove: vec!["^-fconserve-stack$".to_string(), "^-W.*".to_string(), "-O[0-3]".to_string()],
+///     insert: vec!["-D__clangd__".to_string()],
+///     path_rules: vec![PathRule {
+///         path_match: "^/work/zebu/kpar/".to_string(),
+///         insert: vec!["-DKPAR".to_string()],
+///         ..Default::default()
+///     }],
+///     ..Default::default()
+/// };
+/// let (yaml, warnings) = clangd_config::to_clangd_yaml(&ppc, "/work");
+/// assert_eq!(
+///     yaml,
+///     "CompileFlags:\n  Add:\n    - \"-D__clangd__\"\n  Remove:\n    - \"-fconserve-stack\"\n    - \"-W*\"\n\
+///      ---\nIf:\n  PathMatch: \"zebu/kpar/.*\"\nCompileFlags:\n  Add:\n    - \"-DKPAR\"\n"
+/// );
+/// assert_eq!(warnings.len(), 1);
+/// ```
+pub fn to_clangd_yaml(ppc: &PostProcessConfig, base_directory: &str) -> (String, Vec<String>) {
+    let mut warnings = unsupported_rules(ppc);
+    let mut fragments = Vec::new();
+
+    let global = compile_flags_fragment(&ppc.insert, &ppc.remove, &mut warnings);
+    if !global.is_empty() {
+        fragments.push(global);
+    }
+    for rule in &ppc.path_rules {
+        let compile_flags = compile_flags_fragment(&rule.insert, &rule.remove, &mut warnings);
+        if compile_flags.is_empty() {
+            continue;
+        }
+        let path_match = to_path_match(rule, base_directory, &mut warnings);
+        fragments.push(format!(
+            "If:\n  PathMatch: {}\n{}",
+            quote(&path_match),
+            compile_flags
+        ));
+    }
+    (fragments.join("---\n"), warnings)
+}
+
+/// Returns the `CompileFlags` block of a fragment, or an empty string without flags.
+fn compile_flags_fragment(
+    insert: &[String],
+    remove: &[String],
+    warnings: &mut Vec<String>,
+) -> String {
+    let remove = remove
+        .iter()
+        .filter_map(|x| match to_remove_pattern(x) {
+            Ok(pattern) => Some(pattern),
+            Err(reason) => {
+                warnings.push(format!("remove rule {} is skipped: {}", x, reason));
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    if insert.is_empty() && remove.is_empty() {
+        return String::new();
+    }
+
+    let mut fragment = String::from("CompileFlags:\n");
+    for (key, flags) in [("Add", insert), ("Remove", remove.as_slice())] {
+        if flags.is_empty() {
+            continue;
+        }
+        fragment.push_str(&format!("  {}:\n", key));
+        for flag in flags {
+            fragment.push_str(&format!("    - {}\n", quote(flag)));
+        }
+    }
+    fragment
+}
+
+/// Translates a `remove` regex into a clangd `Remove` pattern.
+///
+/// clangd matches a flag exactly, or by prefix with a trailing `*`, so only a literal anchored
+/// at the start of the flag and optionally followed by `.*` can be expressed. A regex matching
+/// an option and its separate value, such as `^-isystem /x$`, cannot be expressed either.
+fn to_remove_pattern(regex: &str) -> Result<String, String> {
+    let body = regex.strip_prefix('^').ok_or_else(|| {
+        "the regex is not anchored with ^, so it also matches inside a flag".to_string()
+    })?;
+    let anchored_end = body.ends_with('$') && !body.ends_with("\\$");
+    let body = if anchored_end {
+        &body[..body.len() - 1]
+    } else {
+        body
+    };
+    let (literal, is_prefix) = match body.strip_suffix(".*") {
+        Some(literal) => (literal, true),
+        None => (body, !anchored_end),
+    };
+    let literal = unescape_literal(literal)
+        .ok_or_else(|| "clangd only matches a flag exactly or by prefix".to_string())?;
+    if !literal.starts_with('-') {
+        return Err("clangd only removes flags starting with -".to_string());
+    }
+    if literal.contains(' ') {
+        return Err("clangd cannot remove an option together with its separate value".to_string());
+    }
+    Ok(if is_prefix { literal + "*" } else { literal })
+}
+
+/// Translates the path regex of a path rule into a clangd `PathMatch`, which must match the
+/// whole relative path.
+fn to_path_match(rule: &PathRule, base_directory: &str, warnings: &mut Vec<String>) -> String {
+    let mut regex = rule.path_match.clone();
+    let anchored_start = regex.starts_with('^');
+    if anchored_start {
+        regex.remove(0);
+        let base = format!("{}/", base_directory.trim_end_matches('/'));
+        match regex.strip_prefix(&base) {
+            Some(relative) => regex = relative.to_string(),
+            None if regex.starts_with('/') => warnings.push(format!(
+                "path rule {} is outside of {} and will not match in clangd",
+                rule.path_match, base_directory
+            )),
+            None => {}
+        }
+    } else {
+        regex = format!(".*{}", regex);
+    }
+    match regex.strip_suffix('$') {
+        Some(anchored) => anchored.to_string(),
+        None => format!("{}.*", regex),
+    }
+}
+
+/// Returns a warning for every rule of the config that clangd cannot express.
+fn unsupported_rules(ppc: &PostProcessConfig) -> Vec<String> {
+    let mut warnings = ppc
+        .replace
+        .iter()
+        .map(|x| format!("replace rule {} cannot be expressed in .clangd", x))
+        .collect::<Vec<_>>();
+    if !ppc.compiler_map.is_empty() {
+        warnings.push(
+            "compiler_map and its flag translation cannot be expressed per compiler in .clangd"
+                .to_string(),
+        );
+    }
+    let filters = [
+        &ppc.ignore_files,
+        &ppc.only_files,
+        &ppc.ignore_directories,
+        &ppc.only_directories,
+        &ppc.ignore_compilers,
+        &ppc.only_compilers,
+        &ppc.ignore_outputs,
+        &ppc.only_outputs,
+        &ppc.ignore_flags,
+        &ppc.only_flags,
+        &ppc.ignore_inputs,
+        &ppc.only_inputs,
+    ];
+    if filters.iter().any(|x| !x.is_empty()) {
+        warnings.push("entry filters cannot be expressed in .clangd".to_string());
+    }
+    if !ppc.prefer.is_empty() {
+        warnings.push("prefer rules cannot be expressed in .clangd".to_string());
+    }
+    warnings
+}
+
+/// Returns the literal text of a regex without metacharacters, or `None` if it has any.
+fn unescape_literal(regex: &str) -> Option<String> {
+    let mut literal = String::new();
+    let mut chars = regex.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let escaped = chars.next()?;
+            if !REGEX_META_CHARACTERS.contains(escaped) && escaped != '-' {
+                return None;
+            }
+            literal.push(escaped);
+        } else if REGEX_META_CHARACTERS.contains(c) {
+            return None;
+        } else {
+            literal.push(c);
+        }
+    }
+    Some(literal)
+}
+
+/// Quotes a string as a YAML double-quoted scalar.
+fn quote(text: &str) -> String {
+    serde_json::to_string(text).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_patterns_are_exact_or_prefix() {
+        assert_eq!(to_remove_pattern("^-Werror$"), Ok("-Werror".to_string()));
+        assert_eq!(to_remove_pattern("^-W.*"), Ok("-W*".to_string()));
+        assert_eq!(to_remove_pattern("^-Werror"), Ok("-Werror*".to_string()));
+        assert_eq!(to_remove_pattern("^-f\\+\\+$"), Ok("-f++".to_string()));
+    }
+
+    #[test]
+    fn inexact_remove_patterns_are_skipped() {
+        assert!(to_remove_pattern("-Werror").is_err());
+        assert!(to_remove_pattern("-W.*").is_err());
+        assert!(to_remove_pattern("^-O[0-3]$").is_err());
+        assert!(to_remove_pattern("^/work/.*").is_err());
+        assert!(to_remove_pattern("^-isystem /x$").is_err());
+    }
+}
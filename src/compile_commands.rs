@@ -1,9 +1,96 @@
-use crate::postprocess_config::PostProcessConfig;
+use crate::flag_translation::FlagTranslator;
+use crate::postprocess_config::{CompiledPathRule, PostProcessConfig, PreferRule};
+use regex::Regex;
 use relative_path::RelativePath;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Compiler launchers that run the real compiler given as their next argument.
+const KNOWN_COMPILER_WRAPPERS: [&str; 4] = ["ccache", "distcc", "icecc", "sccache"];
+
+/// Options whose value is the next argument and that only matter to the build itself.
+const BUILD_ONLY_OPTIONS_WITH_VALUE: [&str; 4] = ["-o", "-MF", "-MT", "-MQ"];
+
+/// Options that only matter to the build itself.
+const BUILD_ONLY_OPTIONS: [&str; 4] = ["-c", "-MD", "-MMD", "-MP"];
+
+/// Options taking their value as the next argument when it is not glued to them.
+const SEPARATE_VALUE_OPTIONS: [&str; 20] = [
+    "-I",
+    "-isystem",
+    "-iquote",
+    "-idirafter",
+    "-isysroot",
+    "--sysroot",
+    "-include",
+    "-imacros",
+    "-D",
+    "-U",
+    "-x",
+    "-o",
+    "-MF",
+    "-MT",
+    "-MQ",
+    "-Xclang",
+    "-Xpreprocessor",
+    "-Xassembler",
+    "-Xlinker",
+    "-target",
+];
+
+/// The options merged by `deduplicate_with_merge`, glued to their value or not.
+const MERGED_OPTIONS: [&str; 4] = ["-isystem", "-iquote", "-I", "-D"];
+
+/// Source file extensions and the `-x` language they are compiled as.
+const EXTENSION_LANGUAGES: [(&str, &str); 16] = [
+    ("c", "c"),
+    ("cc", "c++"),
+    ("cpp", "c++"),
+    ("cxx", "c++"),
+    ("c++", "c++"),
+    ("C", "c++"),
+    ("cu", "cuda"),
+    ("m", "objective-c"),
+    ("mm", "objective-c++"),
+    ("h", "c-header"),
+    ("hh", "c++-header"),
+    ("hpp", "c++-header"),
+    ("hxx", "c++-header"),
+    ("h++", "c++-header"),
+    ("inl", "c++-header"),
+    ("ipp", "c++-header"),
+];
+
+/// The attributes identifying duplicate compile commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupKey {
+    /// The normalized source path.
+    File,
+    /// The normalized source path and the output, keeping build variants of a file apart.
+    FileAndOutput,
+}
+
+/// The attribute the output is sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// The `file` field.
+    File,
+    /// The `directory` field.
+    Directory,
+    /// The `output` field.
+    Output,
+}
+
+/// The layout of the written compile database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One indented object per entry.
+    Pretty,
+    /// One single-line object per entry.
+    Compact,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct CompileCommand {
     #[serde(default)]
     pub command: String,
@@ -13,6 +100,32 @@ pub struct CompileCommand {
     pub file: String,
     #[serde(default)]
     pub output: String,
+    /// The compile database and the position in it that this entry was read from.
+    #[serde(skip)]
+    pub source: EntrySource,
+}
+
+/// The compile database and the position in it that an entry was read from.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntrySource {
+    /// The path of the compile database.
+    pub path: String,
+    /// The index of the entry in the compile database.
+    pub index: usize,
+}
+
+impl std::fmt::Display for EntrySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}#{}", self.path, self.index)
+    }
+}
+
+/// A compile command serialized together with its source.
+#[derive(Serialize)]
+struct CompileCommandWithSource<'a> {
+    #[serde(flatten)]
+    compile_command: &'a CompileCommand,
+    source: &'a EntrySource,
 }
 
 impl CompileCommand {
@@ -22,12 +135,16 @@ impl CompileCommand {
     ///
     /// * `&mut self` - The compile command to be post-processed.
     /// * `pp_config` - The post-processing configuration.
+    /// * `flag_translator` - The compiler substitution built from `pp_config`, if any.
+    /// * `path_rules` - The path rules of `pp_config`; the matching ones add their `remove` and
+    ///   `insert` rules.
     ///
     /// # Examples
     ///
     /// ```
-    /// use crate::compile_commands::CompileCommand;
-    /// use crate::postprocess_config::PostProcessConfig;
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    /// use ccj_postprocess::flag_translation::FlagTranslator;
+    /// use ccj_postprocess::postprocess_config::PostProcessConfig;
     ///
     /// let mut cc = CompileCommand {
     ///     command: "g++ -I. -DNDEBUG -o test test.cpp".to_string(),
@@ -35,22 +152,59 @@ impl CompileCommand {
     ///     directory: "/path/to/project".to_string(),
     ///     file: "test.cpp".to_string(),
     ///     output: "test".to_string(),
+    ///     ..Default::default()
     /// };
     /// let pp_config = Some(PostProcessConfig::default());
-    /// cc.postprocess(&pp_config);
+    /// let flag_translator = pp_config.as_ref().map(FlagTranslator::from_config);
+    /// cc.postprocess(&pp_config, flag_translator.as_ref(), &[]);
     /// ```
-    pub fn postprocess(&mut self, pp_config: &Option<PostProcessConfig>) {
+    pub fn postprocess(
+        &mut self,
+        pp_config: &Option<PostProcessConfig>,
+        flag_translator: Option<&FlagTranslator>,
+        path_rules: &[CompiledPathRule],
+    ) {
         self.init_arguments();
+        let source_path = self.source_path();
         let arguments = &mut self.arguments;
 
+        // strip or skip the compiler wrappers like ccache
+        let wrappers = pp_config
+            .as_ref()
+            .map(|x| x.compiler_wrappers.clone())
+            .unwrap_or_default();
+        if pp_config
+            .as_ref()
+            .is_some_and(|x| x.strip_compiler_wrappers == Some(true))
+        {
+            let compiler_index = Self::compiler_index(arguments, &wrappers);
+            arguments.drain(..compiler_index);
+        }
+
         Self::remove_duplicate_option(arguments);
         Self::handle_include_path(arguments, &self.directory);
 
+        // substitute the compiler and translate the options it does not understand
+        let compiler_index = Self::compiler_index(arguments, &wrappers);
+        if let Some(translator) = flag_translator &&
+            translator.substitute_compiler(arguments, compiler_index)
+        {
+            translator.translate(arguments, compiler_index);
+        }
+
+        // the rules scoped to the files matching their path regex
+        let path_rules = path_rules
+            .iter()
+            .filter(|x| x.path_match.is_match(&source_path))
+            .map(|x| &x.rule)
+            .collect::<Vec<_>>();
+
         // remove the unnessesary options
-        let remove_option = pp_config
+        let mut remove_option = pp_config
             .as_ref()
             .map(|x| x.remove.clone())
-            .unwrap_or(Vec::new());
+            .unwrap_or_default();
+        remove_option.extend(path_rules.iter().flat_map(|rule| rule.remove.clone()));
 
         Self::remove_option(arguments, remove_option);
 
@@ -58,15 +212,18 @@ impl CompileCommand {
         let replace_config = pp_config
             .as_ref()
             .map(|x| x.replace.clone())
-            .unwrap_or(Vec::new());
+            .unwrap_or_default();
         Self::replace_option(arguments, replace_config);
 
         // insert needed options
-        let insert_option = pp_config
+        let mut insert_option = pp_config
             .as_ref()
             .map(|x| x.insert.clone())
-            .unwrap_or(Vec::new());
-        Self::insert_needed_option(arguments, insert_option);
+            .unwrap_or_default();
+        insert_option.extend(path_rules.iter().flat_map(|rule| rule.insert.clone()));
+        // the removals may have dropped a wrapper or anything before the compiler
+        let compiler_index = Self::compiler_index(arguments, &wrappers);
+        Self::insert_needed_option(arguments, insert_option, compiler_index);
 
         Self::remove_duplicate_option(arguments);
 
@@ -84,16 +241,23 @@ impl CompileCommand {
     /// # Examples
     ///
     /// ```no_run
-    /// use crate::compile_commands::CompileCommand;
+    /// use ccj_postprocess::compile_commands::CompileCommand;
     ///
     /// let compile_commands = CompileCommand::parse("compile_commands.json");
     /// ```
     pub fn parse(file: &str) -> Vec<CompileCommand> {
         let path = Path::new(file);
-        let context =
-            std::fs::read_to_string(path).expect(&format!("cannot open the file {:?}", path));
-        serde_json::from_str::<Vec<CompileCommand>>(&context)
-            .expect(&format!("[Error] json file {:?} parse fail!", path))
+        let context = std::fs::read_to_string(path)
+            .unwrap_or_else(|_| panic!("cannot open the file {:?}", path));
+        let mut compile_commands = serde_json::from_str::<Vec<CompileCommand>>(&context)
+            .unwrap_or_else(|_| panic!("[Error] json file {:?} parse fail!", path));
+        for (index, compile_command) in compile_commands.iter_mut().enumerate() {
+            compile_command.source = EntrySource {
+                path: file.to_string(),
+                index,
+            };
+        }
+        compile_commands
     }
 
     /// Dumps a slice of `CompileCommand` structs to the console in a JSON format.
@@ -101,53 +265,563 @@ impl CompileCommand {
     /// # Arguments
     ///
     /// * `compile_commands` - The slice of `CompileCommand` structs to be dumped.
+    /// * `with_source` - Whether to write the source database of each entry as a `source` field.
+    /// * `format` - The layout of the JSON text.
     ///
     /// # Examples
     ///
     /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// use ccj_postprocess::compile_commands::{CompileCommand, OutputFormat};
+    ///
+    /// let compile_commands = vec![];
+    /// CompileCommand::dump_ccj(&compile_commands, false, OutputFormat::Pretty);
+    /// ```
+    pub fn dump_ccj(compile_commands: &[CompileCommand], with_source: bool, format: OutputFormat) {
+        print!("{}", Self::ccj_string(compile_commands, with_source, format));
+    }
+
+    /// Writes a slice of `CompileCommand` structs to a file in the same JSON format as `dump_ccj`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the written compile database.
+    /// * `compile_commands` - The slice of `CompileCommand` structs to be written.
+    /// * `with_source` - Whether to write the source database of each entry as a `source` field.
+    /// * `format` - The layout of the JSON text.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::compile_commands::{CompileCommand, OutputFormat};
     ///
     /// let compile_commands = vec![];
-    /// CompileCommand::dump_ccj(&compile_commands);
+    /// CompileCommand::write_ccj(
+    ///     "build/compile_commands.json",
+    ///     &compile_commands,
+    ///     false,
+    ///     OutputFormat::Compact,
+    /// );
     /// ```
-    pub fn dump_ccj(compile_commands: &[CompileCommand]) {
-        println!("[");
-        compile_commands[0].dump_one_ccj();
-        for cc in &compile_commands[1..] {
-            println!(",");
-            cc.dump_one_ccj();
+    pub fn write_ccj(
+        path: &str,
+        compile_commands: &[CompileCommand],
+        with_source: bool,
+        format: OutputFormat,
+    ) {
+        let context = Self::ccj_string(compile_commands, with_source, format);
+        std::fs::write(path, context).unwrap_or_else(|_| panic!("cannot write the file {}", path));
+    }
+
+    /// Serializes a slice of `CompileCommand` structs as a JSON array.
+    ///
+    /// The pretty format writes each entry as an indented object, the compact format writes
+    /// each entry on a single line. Equal slices always give identical bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `compile_commands` - The slice of `CompileCommand` structs to be serialized.
+    /// * `with_source` - Whether to write the source database of each entry as a `source` field.
+    /// * `format` - The layout of the JSON text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::{CompileCommand, OutputFormat};
+    ///
+    /// let cc = CompileCommand {
+    ///     command: "g++ -c a.cc".to_string(),
+    ///     arguments: vec![],
+    ///     directory: "/".to_string(),
+    ///     file: "a.cc".to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(
+    ///     CompileCommand::ccj_string(&[cc.clone(), cc], false, OutputFormat::Compact),
+    ///     "[\n{\"command\":\"g++ -c a.cc\",\"arguments\":[],\"directory\":\"/\",\"file\":\"a.cc\",\"output\":\"\"},\n\
+    ///      {\"command\":\"g++ -c a.cc\",\"arguments\":[],\"directory\":\"/\",\"file\":\"a.cc\",\"output\":\"\"}\n]\n"
+    /// );
+    /// ```
+    pub fn ccj_string(
+        compile_commands: &[CompileCommand],
+        with_source: bool,
+        format: OutputFormat,
+    ) -> String {
+        if compile_commands.is_empty() {
+            return "[\n]\n".to_string();
         }
-        println!("]");
+        let entries = compile_commands
+            .iter()
+            .map(|x| x.one_ccj_string(with_source, format))
+            .collect::<Vec<_>>();
+        let separator = match format {
+            OutputFormat::Pretty => "\n,\n",
+            OutputFormat::Compact => ",\n",
+        };
+        format!("[\n{}\n]\n", entries.join(separator))
+    }
+
+    /// Sorts compile commands by a key, so the output does not depend on the input order.
+    ///
+    /// Entries with the same key are ordered by source path, directory, output and command.
+    ///
+    /// # Arguments
+    ///
+    /// * `compile_commands` - The vector of `CompileCommand` structs to be sorted.
+    /// * `sort_key` - The attribute sorted by.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::{CompileCommand, SortKey};
+    ///
+    /// let entry = |directory: &str, file: &str| CompileCommand {
+    ///     command: "".to_string(),
+    ///     arguments: vec![],
+    ///     directory: directory.to_string(),
+    ///     file: file.to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let mut compile_commands = vec![entry("/b", "a.cc"), entry("/a", "z.cc"), entry("/a", "b.cc")];
+    /// CompileCommand::sort(&mut compile_commands, SortKey::File);
+    /// assert_eq!(compile_commands[0].file, "a.cc");
+    /// CompileCommand::sort(&mut compile_commands, SortKey::Directory);
+    /// assert_eq!(compile_commands[0].file, "b.cc");
+    /// ```
+    pub fn sort(compile_commands: &mut [CompileCommand], sort_key: SortKey) {
+        compile_commands.sort_by_cached_key(|cc| {
+            let key = match sort_key {
+                SortKey::File => cc.file.clone(),
+                SortKey::Directory => cc.directory.clone(),
+                SortKey::Output => cc.output.clone(),
+            };
+            (
+                key,
+                cc.source_path(),
+                cc.directory.clone(),
+                cc.output.clone(),
+                cc.command.clone(),
+            )
+        });
     }
+
+    /// Overlays compile commands on a base, replacing the base entries of the same file.
+    ///
+    /// An overlay entry takes the place of the first base entry with the same normalized
+    /// source path, and the other base entries of that file are dropped. Overlay entries of
+    /// new files are appended.
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - The vector of `CompileCommand` structs to be patched.
+    /// * `overlay` - The vector of `CompileCommand` structs replacing base entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    ///
+    /// let entry = |file: &str, command: &str| CompileCommand {
+    ///     command: command.to_string(),
+    ///     arguments: vec![],
+    ///     directory: "/work".to_string(),
+    ///     file: file.to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let mut base = vec![entry("a.cc", "g++ -O2 -c a.cc"), entry("b.cc", "g++ -O2 -c b.cc")];
+    /// let overlay = vec![entry("/work/a.cc", "g++ -O0 -c a.cc"), entry("c.cc", "g++ -c c.cc")];
+    /// CompileCommand::overlay(&mut base, overlay);
+    /// let commands = base.iter().map(|x| x.command.as_str()).collect::<Vec<_>>();
+    /// assert_eq!(commands, vec!["g++ -O0 -c a.cc", "g++ -O2 -c b.cc", "g++ -c c.cc"]);
+    /// ```
+    pub fn overlay(base: &mut Vec<CompileCommand>, overlay: Vec<CompileCommand>) {
+        let mut replacements = std::collections::HashMap::<String, Vec<CompileCommand>>::new();
+        let mut overlay_files = Vec::new();
+        for compile_command in overlay {
+            let key = compile_command.source_path();
+            if !replacements.contains_key(&key) {
+                overlay_files.push(key.clone());
+            }
+            replacements.entry(key).or_default().push(compile_command);
+        }
+
+        let mut patched_files = std::collections::HashSet::new();
+        let mut patched = Vec::with_capacity(base.len());
+        for compile_command in base.drain(..) {
+            let key = compile_command.source_path();
+            if patched_files.contains(&key) {
+                // the file is already replaced by the overlay
+                continue;
+            }
+            match replacements.remove(&key) {
+                Some(mut replacement) => {
+                    patched.append(&mut replacement);
+                    patched_files.insert(key);
+                }
+                None => patched.push(compile_command),
+            }
+        }
+        for key in overlay_files {
+            if let Some(mut replacement) = replacements.remove(&key) {
+                patched.append(&mut replacement);
+            }
+        }
+        *base = patched;
+    }
+
+    /// Removes the compile commands of the listed files.
+    ///
+    /// An absolute listed file matches the normalized source path of an entry. A relative
+    /// one, such as a bare file name, matches the source paths ending with it, but only when
+    /// they all name the same file; an ambiguous name is reported on stderr and removes
+    /// nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `compile_commands` - The vector of `CompileCommand` structs to be filtered.
+    /// * `files` - The files whose compile commands are removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    ///
+    /// let entry = |directory: &str, file: &str| CompileCommand {
+    ///     command: "g++ -c".to_string(),
+    ///     arguments: vec![],
+    ///     directory: directory.to_string(),
+    ///     file: file.to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let mut compile_commands = vec![
+    ///     entry("/work", "a.cc"),
+    ///     entry("/work/x", "b.cc"),
+    ///     entry("/work/y", "b.cc"),
+    ///     entry("/work", "c/d.cc"),
+    /// ];
+    /// let files = ["/work/./a.cc", "b.cc", "d.cc"].map(String::from);
+    /// CompileCommand::remove_entries(&mut compile_commands, &files);
+    /// let left = compile_commands.iter().map(|x| x.source_path()).collect::<Vec<_>>();
+    /// assert_eq!(left, vec!["/work/x/b.cc", "/work/y/b.cc"]);
+    /// ```
+    pub fn remove_entries(compile_commands: &mut Vec<CompileCommand>, files: &[String]) {
+        let mut removed = std::collections::HashSet::new();
+        for file in files {
+            let path = Path::new(file);
+            if path.is_absolute() {
+                removed.insert(Self::normalize_path(path));
+                continue;
+            }
+            let suffix = format!("/{}", Self::normalize_path(path));
+            let matched = compile_commands
+                .iter()
+                .map(|x| x.source_path())
+                .filter(|x| x.ends_with(&suffix))
+                .collect::<std::collections::BTreeSet<_>>();
+            if matched.len() > 1 {
+                eprintln!(
+                    "[Warning] {} matches {} files, keep them; list the full path instead",
+                    file,
+                    matched.len()
+                );
+                continue;
+            }
+            removed.extend(matched);
+        }
+        compile_commands.retain(|x| !removed.contains(&x.source_path()));
+    }
+
     /// Removes duplicate compile commands from a vector, keeping the first occurrence.
     ///
+    /// Two compile commands are duplicates when their `DedupKey` attributes are equal.
+    ///
     /// # Arguments
     ///
     /// * `compile_commands` - The vector of `CompileCommand` structs to be deduplicated.
+    /// * `dedup_key` - The attributes identifying duplicates.
     ///
     /// # Examples
     ///
     /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// use ccj_postprocess::compile_commands::{CompileCommand, DedupKey};
     ///
     /// let compile_commands = vec![];
-    /// let deduped_commands = CompileCommand::deduplicate_with_retain_first(compile_commands);
+    /// let deduped_commands =
+    ///     CompileCommand::deduplicate_with_retain_first(compile_commands, DedupKey::File);
     /// ```
     pub fn deduplicate_with_retain_first(
         mut compile_commands: Vec<CompileCommand>,
+        dedup_key: DedupKey,
     ) -> Vec<CompileCommand> {
         let mut tmp_compile_commands = Vec::with_capacity(compile_commands.len());
         std::mem::swap(&mut tmp_compile_commands, &mut compile_commands);
         let mut hs = std::collections::HashSet::new();
         for compile_command in tmp_compile_commands {
-            let key = compile_command.directory.clone() + &compile_command.file;
+            let key = compile_command.dedup_key(dedup_key);
             if hs.insert(key) {
                 compile_commands.push(compile_command);
             }
         }
         compile_commands
     }
-    /// Processes the compile commands based on a `PostProcessConfig`, likely filtering out ignored files.
+    /// Merges duplicate compile commands into the first occurrence.
+    ///
+    /// The include directories (`-I`, `-isystem`, `-iquote`) and defines of the merged entry
+    /// are the ordered union of all duplicates, whether the value is glued to the option or
+    /// is the next argument. A define with conflicting values or a differing `-std` level is
+    /// reported on stderr, and the value of the first occurrence is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `compile_commands` - The vector of `CompileCommand` structs to be merged.
+    /// * `dedup_key` - The attributes identifying duplicates.
+    /// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::{CompileCommand, DedupKey};
+    ///
+    /// let entry = |command: &str| CompileCommand {
+    ///     command: command.to_string(),
+    ///     arguments: vec![],
+    ///     directory: "/work".to_string(),
+    ///     file: "a.cc".to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let compile_commands = vec![
+    ///     entry("g++ -I/inc/a -DA=1 -c a.cc"),
+    ///     entry("g++ -I/inc/b -I/inc/a -DB -DA=2 -c a.cc"),
+    /// ];
+    /// let merged = CompileCommand::deduplicate_with_merge(compile_commands, DedupKey::File, &[]);
+    /// assert_eq!(merged.len(), 1);
+    /// assert_eq!(merged[0].arguments, vec!["g++", "-I/inc/a", "-I/inc/b", "-DA=1", "-DB", "-c", "a.cc"]);
+    /// ```
+    pub fn deduplicate_with_merge(
+        compile_commands: Vec<CompileCommand>,
+        dedup_key: DedupKey,
+        extra_wrappers: &[String],
+    ) -> Vec<CompileCommand> {
+        let mut merged: Vec<CompileCommand> = Vec::with_capacity(compile_commands.len());
+        let mut position = std::collections::HashMap::new();
+        for mut compile_command in compile_commands {
+            compile_command.init_arguments();
+            Self::handle_include_path(&mut compile_command.arguments, &compile_command.directory);
+            let key = compile_command.dedup_key(dedup_key);
+            match position.get(&key) {
+                Some(&index) => {
+                    let base: &mut CompileCommand = &mut merged[index];
+                    base.merge_flags(&compile_command, &key.0, extra_wrappers);
+                }
+                None => {
+                    position.insert(key, merged.len());
+                    merged.push(compile_command);
+                }
+            }
+        }
+        merged
+    }
+
+    /// Merges the include directories and defines of a duplicate into this compile command.
+    fn merge_flags(&mut self, duplicate: &CompileCommand, path: &str, extra_wrappers: &[String]) {
+        let define_name = |value: &str| value.split('=').next().unwrap_or("").to_string();
+        let std_level = |args: &[String]| args.iter().rfind(|x| x.starts_with("-std=")).cloned();
+
+        if let (Some(base_std), Some(dup_std)) =
+            (std_level(&self.arguments), std_level(&duplicate.arguments)) &&
+            base_std != dup_std
+        {
+            eprintln!(
+                "[Warning] {}: conflicting language standards {} and {}, keep {}",
+                path, base_std, dup_std, base_std
+            );
+        }
+
+        for dup in Self::merged_options(&duplicate.arguments, extra_wrappers) {
+            let base = Self::merged_options(&self.arguments, extra_wrappers);
+            let same = |x: &&MergedOption| {
+                x.option == dup.option &&
+                    if dup.option == "-D" {
+                        define_name(&x.value) == define_name(&dup.value)
+                    } else {
+                        x.value == dup.value
+                    }
+            };
+            match base.iter().find(same) {
+                Some(kept) if kept.value != dup.value => eprintln!(
+                    "[Warning] {}: conflicting defines {} and {}, keep {}",
+                    path,
+                    kept.text(&self.arguments),
+                    dup.text(&duplicate.arguments),
+                    kept.text(&self.arguments)
+                ),
+                Some(_) => {}
+                None => {
+                    // after the last option of the same kind, or right after the compiler
+                    let position = base
+                        .iter()
+                        .rfind(|x| x.option == dup.option)
+                        .map(|x| x.range.end)
+                        .unwrap_or_else(|| {
+                            (Self::compiler_index(&self.arguments, extra_wrappers) + 1)
+                                .min(self.arguments.len())
+                        });
+                    let tokens = duplicate.arguments[dup.range.clone()].to_vec();
+                    self.arguments.splice(position..position, tokens);
+                }
+            }
+        }
+    }
+
+    /// Returns the include directories and defines after the compiler, in either form.
+    fn merged_options(arguments: &[String], extra_wrappers: &[String]) -> Vec<MergedOption> {
+        let start = (Self::compiler_index(arguments, extra_wrappers) + 1).min(arguments.len());
+        let mut options = Vec::new();
+        let mut index = start;
+        for group in Self::option_groups(&arguments[start..]) {
+            let range = index..index + group.len();
+            index = range.end;
+            let merged = MERGED_OPTIONS.iter().find_map(|option| match group {
+                [x, value] if x == option => Some((*option, value.clone())),
+                [x] if x.len() > option.len() && x.starts_with(option) => {
+                    Some((*option, x[option.len()..].to_string()))
+                }
+                _ => None,
+            });
+            if let Some((option, value)) = merged {
+                options.push(MergedOption {
+                    option,
+                    value,
+                    range,
+                });
+            }
+        }
+        options
+    }
+
+    /// Splits the arguments into options, each together with its value when it is the next
+    /// argument, so that `-isystem /inc` stays one unit while `-I/inc` is one on its own.
+    ///
+    /// # Arguments
+    ///
+    /// * `arguments` - The command-line arguments, usually the ones after the compiler.
+    ///
+    /// # Returns
+    ///
+    /// - `Vec<&[String]>` - The options, one or two arguments each, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    ///
+    /// let args = ["-isystem", "/sys", "-I/inc", "-D", "X", "-O2"].map(String::from);
+    /// let groups = CompileCommand::option_groups(&args);
+    /// assert_eq!(groups, vec![&args[0..2], &args[2..3], &args[3..5], &args[5..6]]);
+    /// ```
+    pub fn option_groups(arguments: &[String]) -> Vec<&[String]> {
+        let mut groups = Vec::new();
+        let mut index = 0;
+        while index < arguments.len() {
+            let length = if SEPARATE_VALUE_OPTIONS.contains(&arguments[index].as_str()) {
+                2.min(arguments.len() - index)
+            } else {
+                1
+            };
+            groups.push(&arguments[index..index + length]);
+            index += length;
+        }
+        groups
+    }
+
+    /// Picks one variant per source file according to the prefer rules.
+    ///
+    /// The rules are tried in order and the first variant matching the first applicable rule
+    /// is kept. When no rule matches, the first variant is kept. Without rules, every
+    /// variant is kept. It runs before `postprocess`, so the flag rules match the arguments
+    /// as written in the input.
+    ///
+    /// # Arguments
+    ///
+    /// * `compile_commands` - The vector of `CompileCommand` structs to pick from.
+    /// * `prefer` - The prefer rules of the postprocess config.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    /// use ccj_postprocess::postprocess_config::PreferRule;
+    ///
+    /// let entry = |command: &str, output: &str| CompileCommand {
+    ///     command: command.to_string(),
+    ///     arguments: vec![],
+    ///     directory: "/work".to_string(),
+    ///     file: "a.cc".to_string(),
+    ///     output: output.to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let compile_commands = vec![entry("g++ -O2 -c a.cc", "release/a.o"), entry("g++ -O0 -c a.cc", "debug/a.o")];
+    /// let prefer = vec![PreferRule::Flag("^-O0$".to_string())];
+    /// let selected = CompileCommand::select_preferred(compile_commands, &prefer);
+    /// assert_eq!(selected.len(), 1);
+    /// assert_eq!(selected[0].output, "debug/a.o");
+    /// ```
+    pub fn select_preferred(
+        compile_commands: Vec<CompileCommand>,
+        prefer: &[PreferRule],
+    ) -> Vec<CompileCommand> {
+        if prefer.is_empty() {
+            return compile_commands;
+        }
+        let rules = prefer
+            .iter()
+            .map(|rule| match rule {
+                PreferRule::Flag(x) => (Regex::new(x).unwrap(), true),
+                PreferRule::Input(x) => (Regex::new(x).unwrap(), false),
+            })
+            .collect::<Vec<_>>();
+        let is_match = |(regex, is_flag): &(Regex, bool), cc: &CompileCommand| {
+            if *is_flag && cc.arguments.is_empty() {
+                cc.command.split(' ').any(|x| regex.is_match(x))
+            } else if *is_flag {
+                cc.arguments.iter().any(|x| regex.is_match(x))
+            } else {
+                regex.is_match(&cc.source.path)
+            }
+        };
+
+        let mut variants: Vec<Vec<CompileCommand>> = Vec::new();
+        let mut position = std::collections::HashMap::<String, usize>::new();
+        for compile_command in compile_commands {
+            let key = compile_command.source_path();
+            match position.get(&key) {
+                Some(&index) => variants[index].push(compile_command),
+                None => {
+                    position.insert(key, variants.len());
+                    variants.push(vec![compile_command]);
+                }
+            }
+        }
+        variants
+            .into_iter()
+            .map(|mut variant| {
+                let index = rules
+                    .iter()
+                    .find_map(|rule| variant.iter().position(|cc| is_match(rule, cc)))
+                    .unwrap_or(0);
+                variant.swap_remove(index)
+            })
+            .collect()
+    }
+    /// Processes the compile commands based on a `PostProcessConfig`, filtering the entries.
+    ///
+    /// Every filter dimension (file path, directory, compiler, output, input and flags) has an
+    /// `only_*` allow-list and an `ignore_*` deny-list of regexes. An entry is kept when
+    /// each non-empty allow-list matches it and no deny-list matches it, so excludes win.
     ///
     /// # Arguments
     ///
@@ -157,27 +831,259 @@ impl CompileCommand {
     /// # Examples
     ///
     /// ```
-    /// use crate::compile_commands::CompileCommand;
-    /// use crate::postprocess_config::PostProcessConfig;
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    /// use ccj_postprocess::postprocess_config::PostProcessConfig;
     ///
-    /// let mut compile_commands = vec![];
-    /// let pp_config = PostProcessConfig::default();
+    /// let entry = |file: &str, command: &str| CompileCommand {
+    ///     command: command.to_string(),
+    ///     arguments: vec![],
+    ///     directory: "/work".to_string(),
+    ///     file: file.to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let mut compile_commands = vec![
+    ///     entry("a.cc", "g++ -std=c++23 -c a.cc"),
+    ///     entry("b.cc", "g++ -std=c++17 -c b.cc"),
+    ///     entry("c.cc", "g++ -std=c++23 -c c.cc"),
+    /// ];
+    /// let pp_config = PostProcessConfig {
+    ///     only_flags: vec!["^-std=c\\+\\+23$".to_string()],
+    ///     ignore_files: vec!["c\\.cc$".to_string()],
+    ///     ..Default::default()
+    /// };
     /// CompileCommand::process_config(&mut compile_commands, &pp_config);
+    /// assert_eq!(compile_commands.len(), 1);
+    /// assert_eq!(compile_commands[0].file, "a.cc");
     /// ```
     pub fn process_config(compile_commands: &mut Vec<CompileCommand>, ppc: &PostProcessConfig) {
-        let ignore_files: Vec<String> = ppc.ignore_files.clone();
-        if !ignore_files.is_empty() {
-            use regex::Regex;
-            let remove_regex = ignore_files
-                .into_iter()
-                .map(|x| Regex::new(&x).unwrap())
+        let filters = [
+            EntryFilter::new(&ppc.only_files, &ppc.ignore_files, |x| {
+                vec![x.source_path()]
+            }),
+            EntryFilter::new(&ppc.only_directories, &ppc.ignore_directories, |x| {
+                vec![x.directory.clone()]
+            }),
+            EntryFilter::new(&ppc.only_compilers, &ppc.ignore_compilers, |x| {
+                vec![x.compiler(&ppc.compiler_wrappers).to_string()]
+            }),
+            EntryFilter::new(&ppc.only_outputs, &ppc.ignore_outputs, |x| {
+                vec![x.output.clone()]
+            }),
+            EntryFilter::new(&ppc.only_inputs, &ppc.ignore_inputs, |x| {
+                vec![x.source.path.clone()]
+            }),
+            EntryFilter::new(&ppc.only_flags, &ppc.ignore_flags, |x| {
+                let compiler_index = Self::compiler_index(&x.arguments, &ppc.compiler_wrappers);
+                x.arguments
+                    .iter()
+                    .skip(compiler_index + 1)
+                    .cloned()
+                    .collect()
+            }),
+        ];
+        if filters.iter().all(|filter| filter.is_empty()) {
+            return;
+        }
+        compile_commands.iter_mut().for_each(|x| x.init_arguments());
+        compile_commands.retain(|x: &CompileCommand| {
+            filters.iter().all(|filter| !filter.is_excluded(x)) &&
+                filters.iter().all(|filter| filter.is_included(x))
+        });
+    }
+
+    /// Returns the compiler of the compile command, skipping leading compiler wrappers.
+    ///
+    /// # Arguments
+    ///
+    /// * `&self` - The compile command.
+    /// * `extra_wrappers` - Wrappers to skip in addition to the known ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    ///
+    /// let cc = CompileCommand {
+    ///     command: "ccache /usr/bin/g++ -c main.cpp".to_string(),
+    ///     arguments: vec![],
+    ///     directory: "/".to_string(),
+    ///     file: "main.cpp".to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(cc.compiler(&[]), "/usr/bin/g++");
+    /// ```
+    pub fn compiler(&self, extra_wrappers: &[String]) -> &str {
+        if self.arguments.is_empty() {
+            let arguments = self
+                .command
+                .split(' ')
+                .map(|x| x.to_string())
                 .collect::<Vec<_>>();
-            compile_commands.retain(|x: &CompileCommand| {
-                let path = x.directory.clone() + "/" + &x.file;
-                remove_regex.iter().all(|regex| !regex.is_match(&path))
-            });
+            let compiler_index = Self::compiler_index(&arguments, extra_wrappers);
+            return self.command.split(' ').nth(compiler_index).unwrap_or("");
+        }
+        let compiler_index = Self::compiler_index(&self.arguments, extra_wrappers);
+        self.arguments
+            .get(compiler_index)
+            .map(|x| x.as_str())
+            .unwrap_or("")
+    }
+
+    /// Detects the language a compile command compiles its source file as.
+    ///
+    /// The last `-x` option before the source file wins. Otherwise the language follows the
+    /// extension, and a C++ driver such as `g++` or `clang++` compiles C files as C++. `-x cu`
+    /// is reported as `cuda`.
+    ///
+    /// # Arguments
+    ///
+    /// * `&self` - The `CompileCommand` to inspect.
+    /// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<String>` - The `-x` name of the language, or `None` for an unknown extension.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    ///
+    /// let entry = |command: &str, file: &str| CompileCommand {
+    ///     command: command.to_string(),
+    ///     arguments: vec![],
+    ///     directory: "/work".to_string(),
+    ///     file: file.to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(entry("gcc -c a.c", "a.c").language(&[]).as_deref(), Some("c"));
+    /// assert_eq!(entry("g++ -c a.c", "a.c").language(&[]).as_deref(), Some("c++"));
+    /// assert_eq!(entry("clang -x cu -c a.cc", "a.cc").language(&[]).as_deref(), Some("cuda"));
+    /// assert_eq!(entry("nvcc -c k.cu", "k.cu").language(&[]).as_deref(), Some("cuda"));
+    /// assert_eq!(entry("gcc -c a.s", "a.s").language(&[]), None);
+    /// ```
+    pub fn language(&self, extra_wrappers: &[String]) -> Option<String> {
+        let arguments = if self.arguments.is_empty() {
+            self.command.split(' ').map(|x| x.to_string()).collect()
+        } else {
+            self.arguments.clone()
+        };
+        let source_path = self.source_path();
+        let source_index = arguments
+            .iter()
+            .position(|x| *x == self.file || *x == source_path)
+            .unwrap_or(arguments.len());
+        let mut explicit = None;
+        let mut iter = arguments[..source_index].iter();
+        while let Some(arg) = iter.next() {
+            match arg.strip_prefix("-x") {
+                Some("") => explicit = iter.next().cloned(),
+                Some(language) => explicit = Some(language.to_string()),
+                None => {}
+            }
+        }
+        if let Some(language) = explicit.filter(|x| x != "none") {
+            return Some(if language == "cu" { "cuda".to_string() } else { language });
+        }
+
+        let extension = Path::new(&self.file).extension()?.to_str()?;
+        let (_, language) = EXTENSION_LANGUAGES.iter().find(|(x, _)| *x == extension)?;
+        let compiler = self.compiler(extra_wrappers);
+        let driver = Path::new(compiler)
+            .file_name()
+            .and_then(|x| x.to_str())
+            .unwrap_or(compiler);
+        let language = match *language {
+            "c" if driver.ends_with("++") => "c++",
+            "c-header" if driver.ends_with("++") => "c++-header",
+            language => language,
+        };
+        Some(language.to_string())
+    }
+
+    /// Returns the flags that affect how the source file is parsed.
+    ///
+    /// The compiler, the source file, the output and the dependency-file options are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `&self` - The postprocessed compile command.
+    /// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    ///
+    /// let cc = CompileCommand {
+    ///     command: "".to_string(),
+    ///     arguments: ["ccache", "g++", "-I/inc", "-MD", "-MF", "a.d", "-c", "a.cc", "-o", "a.o"]
+    ///         .map(String::from)
+    ///         .to_vec(),
+    ///     directory: "/work".to_string(),
+    ///     file: "a.cc".to_string(),
+    ///     output: "a.o".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(cc.compile_flags(&[]), vec!["-I/inc"]);
+    /// ```
+    pub fn compile_flags(&self, extra_wrappers: &[String]) -> Vec<String> {
+        let source_path = self.source_path();
+        let compiler_index = Self::compiler_index(&self.arguments, extra_wrappers);
+        let mut flags = Vec::new();
+        let mut arguments = self.arguments.iter().skip(compiler_index + 1);
+        while let Some(arg) = arguments.next() {
+            if BUILD_ONLY_OPTIONS_WITH_VALUE.contains(&arg.as_str()) {
+                arguments.next();
+            } else if !(BUILD_ONLY_OPTIONS.contains(&arg.as_str()) ||
+                (arg.starts_with("-o") && arg.len() > 2) ||
+                *arg == self.file ||
+                *arg == source_path)
+            {
+                flags.push(arg.clone());
+            }
         }
+        flags
+    }
+
+    /// Returns the index of the real compiler in the arguments.
+    ///
+    /// Leading compiler wrappers such as `ccache` or `distcc` are skipped. A wrapper is
+    /// recognized by its full path or by its file name.
+    ///
+    /// # Arguments
+    ///
+    /// * `arguments` - The command-line arguments.
+    /// * `extra_wrappers` - Wrappers to skip in addition to the known ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    ///
+    /// let args = ["/usr/bin/ccache", "distcc", "g++", "-c", "main.cpp"].map(String::from);
+    /// assert_eq!(CompileCommand::compiler_index(&args, &[]), 2);
+    ///
+    /// let args = ["my_launcher", "g++", "-c", "main.cpp"].map(String::from);
+    /// assert_eq!(CompileCommand::compiler_index(&args, &["my_launcher".to_string()]), 1);
+    /// ```
+    pub fn compiler_index(arguments: &[String], extra_wrappers: &[String]) -> usize {
+        let is_wrapper = |arg: &str| {
+            let name = Path::new(arg)
+                .file_name()
+                .and_then(|x| x.to_str())
+                .unwrap_or(arg);
+            KNOWN_COMPILER_WRAPPERS.contains(&name) ||
+                extra_wrappers.iter().any(|x| x == arg || x == name)
+        };
+        let wrapper_count = arguments.iter().take_while(|x| is_wrapper(x)).count();
+        // the last argument can never be a wrapper without a compiler
+        wrapper_count.min(arguments.len().saturating_sub(1))
     }
+
     /// Prints the full path of the file associated with the compile command.
     ///
     /// # Arguments
@@ -187,7 +1093,7 @@ impl CompileCommand {
     /// # Examples
     ///
     /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// use ccj_postprocess::compile_commands::CompileCommand;
     ///
     /// let cc = CompileCommand {
     ///     command: "".to_string(),
@@ -195,11 +1101,118 @@ impl CompileCommand {
     ///     directory: "/path/to/project".to_string(),
     ///     file: "test.cpp".to_string(),
     ///     output: "".to_string(),
+    ///     ..Default::default()
     /// };
     /// cc.dump_full_path();
     /// ```
     pub fn dump_full_path(&self) {
-        println!("{}/{}", self.directory, self.file);
+        println!("{}", self.source_path());
+    }
+
+    /// Returns the key identifying duplicates of this compile command.
+    fn dedup_key(&self, dedup_key: DedupKey) -> (String, String) {
+        match dedup_key {
+            DedupKey::File => (self.source_path(), String::new()),
+            DedupKey::FileAndOutput => (self.source_path(), self.output.clone()),
+        }
+    }
+
+    /// Checks if the compile command compiles the given file.
+    ///
+    /// The file matches either the `file` field as written in the compile database or the
+    /// normalized source path.
+    ///
+    /// # Arguments
+    ///
+    /// * `&self` - The compile command.
+    /// * `file` - The file to look for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    ///
+    /// let cc = CompileCommand {
+    ///     command: "".to_string(),
+    ///     arguments: vec![],
+    ///     directory: "/path/to/project".to_string(),
+    ///     file: "test.cpp".to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// assert!(cc.is_the_file("test.cpp"));
+    /// assert!(cc.is_the_file("/path/to/project/./test.cpp"));
+    /// assert!(!cc.is_the_file("/path/test.cpp"));
+    /// ```
+    pub fn is_the_file(&self, file: &str) -> bool {
+        self.file == file || self.source_path() == Self::normalize_path(Path::new(file))
+    }
+
+    /// Returns the normalized absolute path of the source file.
+    ///
+    /// An absolute `file` is used as is, otherwise it is resolved against `directory`.
+    ///
+    /// # Arguments
+    ///
+    /// * `&self` - The compile command.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    ///
+    /// let entry = |directory: &str, file: &str| CompileCommand {
+    ///     command: "".to_string(),
+    ///     arguments: vec![],
+    ///     directory: directory.to_string(),
+    ///     file: file.to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(entry("/a/b", "c.cc").source_path(), "/a/b/c.cc");
+    /// assert_eq!(entry("/a", "b/c.cc").source_path(), "/a/b/c.cc");
+    /// assert_eq!(entry("/a/b/", "./../b/c.cc").source_path(), "/a/b/c.cc");
+    /// assert_eq!(entry("/x", "/a/b/c.cc").source_path(), "/a/b/c.cc");
+    /// ```
+    pub fn source_path(&self) -> String {
+        Self::normalize_path(&Path::new(&self.directory).join(&self.file))
+    }
+
+    /// Lexically normalizes a path by removing `.` components and resolving `..` components.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to normalize.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(CompileCommand::normalize_path(Path::new("/a/./b//../c.cc")), "/a/c.cc");
+    /// assert_eq!(CompileCommand::normalize_path(Path::new("../a/b/../c.cc")), "../a/c.cc");
+    /// ```
+    pub fn normalize_path(path: &Path) -> String {
+        use std::path::Component;
+        let mut normalized = std::path::PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if matches!(
+                        normalized.components().next_back(),
+                        None | Some(Component::ParentDir)
+                    ) {
+                        normalized.push("..");
+                    } else {
+                        normalized.pop();
+                    }
+                }
+                other => normalized.push(other),
+            }
+        }
+        normalized.to_string_lossy().into_owned()
     }
 
     /// Checks if a command-line argument is a `-D` option with an equals sign.
@@ -210,8 +1223,8 @@ impl CompileCommand {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// ```ignore
+    /// use ccj_postprocess::compile_commands::CompileCommand;
     ///
     /// assert!(CompileCommand::is_arg_with_d_and_equal("-DNDEBUG=1"));
     /// ```
@@ -234,8 +1247,8 @@ impl CompileCommand {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// ```ignore
+    /// use ccj_postprocess::compile_commands::CompileCommand;
     ///
     /// assert!(CompileCommand::is_need_to_handle_sing_quota("-DEXTERN='abc'"));
     /// ```
@@ -248,9 +1261,7 @@ impl CompileCommand {
         if v.len() != 2 || v[1].len() < 2 {
             return false;
         }
-        if *v[1].as_bytes().first().unwrap() != '\'' as u8 ||
-            *v[1].as_bytes().last().unwrap() != '\'' as u8
-        {
+        if *v[1].as_bytes().first().unwrap() != b'\'' || *v[1].as_bytes().last().unwrap() != b'\'' {
             return false;
         }
         true
@@ -263,8 +1274,8 @@ impl CompileCommand {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// ```ignore
+    /// use ccj_postprocess::compile_commands::CompileCommand;
     ///
     /// let mut args = vec!["-DEXTERN='abc'".to_string()];
     /// CompileCommand::handle_the_single_quote(&mut args);
@@ -296,7 +1307,7 @@ impl CompileCommand {
     /// # Examples
     ///
     /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// use ccj_postprocess::compile_commands::CompileCommand;
     ///
     /// let args = vec!["g++".to_string(), "-DVAR=a b".to_string(), "main.cpp".to_string()];
     /// let command = CompileCommand::join_the_arguments_as_commands(&args);
@@ -306,7 +1317,7 @@ impl CompileCommand {
     /// let command_no_space = CompileCommand::join_the_arguments_as_commands(&args_no_space);
     /// assert_eq!(command_no_space, "g++ -DVAR=ab main.cpp");
     /// ```
-    fn join_the_arguments_as_commands(args: &[String]) -> String {
+    pub fn join_the_arguments_as_commands(args: &[String]) -> String {
         args.iter()
             .map(|arg| {
                 if Self::is_arg_with_d_and_equal(arg) && arg.contains(" ") {
@@ -331,8 +1342,8 @@ impl CompileCommand {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// ```ignore
+    /// use ccj_postprocess::compile_commands::CompileCommand;
     ///
     /// let mut cc = CompileCommand {
     ///     command: "g++ -o main main.cpp".to_string(),
@@ -340,6 +1351,7 @@ impl CompileCommand {
     ///     directory: "/".to_string(),
     ///     file: "main.cpp".to_string(),
     ///     output: "main".to_string(),
+    ///     ..Default::default()
     /// };
     /// cc.init_arguments();
     /// assert_eq!(cc.arguments, vec!["g++", "-o", "main", "main.cpp"]);
@@ -351,6 +1363,7 @@ impl CompileCommand {
     ///     directory: "/".to_string(),
     ///     file: "main.cpp".to_string(),
     ///     output: "main".to_string(),
+    ///     ..Default::default()
     /// };
     /// cc_with_args.init_arguments();
     /// assert_eq!(cc_with_args.arguments, vec!["g++"]);
@@ -367,23 +1380,33 @@ impl CompileCommand {
 
     /// Removes duplicate options from a vector of arguments, keeping the first occurrence.
     ///
+    /// An option with a separate value, such as `-I /x`, is compared together with its value, so
+    /// `-I /x -I /y` keeps both.
+    ///
     /// # Arguments
     ///
     /// * `arguments` - The vector of command-line arguments.
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// ```ignore
+    /// use ccj_postprocess::compile_commands::CompileCommand;
     ///
-    /// let mut args = vec!["-I.".to_string(), "-g".to_string(), "-I.".to_string()];
+    /// let mut args = ["-I.", "-g", "-I.", "-I", "/x", "-I", "/y", "-I", "/x"]
+    ///     .map(|x| x.to_string())
+    ///     .to_vec();
     /// CompileCommand::remove_duplicate_option(&mut args);
-    /// assert_eq!(args, vec!["-I.", "-g"]);
+    /// assert_eq!(args, vec!["-I.", "-g", "-I", "/x", "-I", "/y"]);
     /// ```
     fn remove_duplicate_option(arguments: &mut Vec<String>) {
         let mut hs = std::collections::HashSet::new();
-        // remove the duplicate arguments
-        arguments.retain(|x| hs.insert(x.clone()));
+        // remove the duplicate arguments, an option together with its separate value
+        *arguments = Self::option_groups(arguments)
+            .into_iter()
+            .filter(|group| hs.insert(*group))
+            .flatten()
+            .cloned()
+            .collect();
     }
 
     /// Resolves relative paths in `-I` options to be absolute from the filesystem root.
@@ -398,8 +1421,8 @@ impl CompileCommand {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// ```ignore
+    /// use ccj_postprocess::compile_commands::CompileCommand;
     ///
     /// let mut args = vec!["-I../include".to_string()];
     /// CompileCommand::handle_include_path(&mut args, "/home/user/project/src");
@@ -427,34 +1450,47 @@ impl CompileCommand {
         }
     }
 
-    /// Inserts a vector of options into the arguments list after the first element (the compiler).
+    /// Inserts a vector of options into the arguments list right after the compiler.
     ///
     /// # Arguments
     ///
     /// * `arguments` - The vector of command-line arguments.
     /// * `insert_options` - The vector of options to insert.
+    /// * `compiler_index` - The index of the compiler, past any compiler wrappers.
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// ```ignore
+    /// use ccj_postprocess::compile_commands::CompileCommand;
     ///
     /// let mut args = vec!["g++".to_string(), "-o".to_string(), "main".to_string(), "main.cpp".to_string()];
     /// let insert_options = vec!["-DDEBUG".to_string(), "-Wall".to_string()];
-    /// CompileCommand::insert_needed_option(&mut args, insert_options);
+    /// CompileCommand::insert_needed_option(&mut args, insert_options, 0);
     /// assert_eq!(args, vec!["g++", "-DDEBUG", "-Wall", "-o", "main", "main.cpp"]);
+    ///
+    /// let mut args = vec!["ccache".to_string(), "g++".to_string(), "main.cpp".to_string()];
+    /// CompileCommand::insert_needed_option(&mut args, vec!["-Wall".to_string()], 1);
+    /// assert_eq!(args, vec!["ccache", "g++", "-Wall", "main.cpp"]);
     /// ```
-    fn insert_needed_option(arguments: &mut Vec<String>, mut insert_options: Vec<String>) {
+    fn insert_needed_option(
+        arguments: &mut Vec<String>,
+        mut insert_options: Vec<String>,
+        compiler_index: usize,
+    ) {
         // insert the specified option after first g++ command
         // original: g++ -o main main.cpp
         // after:    g++ -D__GNU__=10 -o main main.cpp
         let len = insert_options.len();
         arguments.append(&mut insert_options);
-        arguments[1..].rotate_right(len);
+        arguments[compiler_index + 1..].rotate_right(len);
     }
 
     /// Removes options from the arguments list that match a given list of regular expressions.
     ///
+    /// A regex containing a space, such as `^-I /x$`, is also matched against an option and its
+    /// separate value joined by a space, and then both are removed. The other regexes only match
+    /// single arguments.
+    ///
     /// # Arguments
     ///
     /// * `arguments` - The vector of command-line arguments.
@@ -462,8 +1498,8 @@ impl CompileCommand {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// ```ignore
+    /// use ccj_postprocess::compile_commands::CompileCommand;
     ///
     /// let mut args = vec!["g++".to_string(), "-g".to_string(), "-O2".to_string(), "-Wall".to_string()];
     /// let remove_options = vec!["-g".to_string(), "-O.".to_string()];
@@ -476,7 +1512,20 @@ impl CompileCommand {
             .into_iter()
             .map(|x| Regex::new(&x).unwrap())
             .collect::<Vec<_>>();
-        arguments.retain(|x| remove_regex.iter().all(|regex| !regex.is_match(x)));
+        let pair_regex = remove_regex
+            .iter()
+            .filter(|regex| regex.as_str().contains(' '))
+            .collect::<Vec<_>>();
+        *arguments = Self::option_groups(arguments)
+            .into_iter()
+            .filter(|group| {
+                let joined = group.join(" ");
+                group.len() == 1 || pair_regex.iter().all(|regex| !regex.is_match(&joined))
+            })
+            .flatten()
+            .filter(|x| remove_regex.iter().all(|regex| !regex.is_match(x)))
+            .cloned()
+            .collect();
         // arguments.retain(|x| !remove_options.contains(x));
     }
 
@@ -492,8 +1541,8 @@ impl CompileCommand {
     ///
     /// # Examples
     ///
-    /// ```
-    /// use crate::compile_commands::CompileCommand;
+    /// ```ignore
+    /// use ccj_postprocess::compile_commands::CompileCommand;
     ///
     /// let mut args = vec!["-O2".to_string(), "--param=val1".to_string()];
     /// let replace_config = vec!["-O2,-O3".to_string(), "val1,val2".to_string()];
@@ -511,28 +1560,225 @@ impl CompileCommand {
         }
     }
 
-    /// Dumps a single `CompileCommand` to the console in a pretty JSON format.
-    ///
-    /// # Arguments
-    ///
-    /// * `&self` - The `CompileCommand` to dump.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use crate::compile_commands::CompileCommand;
-    ///
-    /// let cc = CompileCommand {
-    ///     command: "g++".to_string(),
-    ///     arguments: vec!["g++".to_string()],
-    ///     directory: "/".to_string(),
-    ///     file: "a.cpp".to_string(),
-    ///     output: "a.o".to_string(),
-    /// };
-    /// // This will print the CompileCommand as a JSON object to stdout.
-    /// cc.dump_one_ccj();
-    /// ```
-    fn dump_one_ccj(&self) {
-        println!("{}", serde_json::to_string_pretty(self).unwrap());
+    /// Serializes a single compile command as a JSON object.
+    fn one_ccj_string(&self, with_source: bool, format: OutputFormat) -> String {
+        fn to_string<T: Serialize>(value: &T, format: OutputFormat) -> String {
+            match format {
+                OutputFormat::Pretty => serde_json::to_string_pretty(value).unwrap(),
+                OutputFormat::Compact => serde_json::to_string(value).unwrap(),
+            }
+        }
+        if with_source {
+            let with_source = CompileCommandWithSource {
+                compile_command: self,
+                source: &self.source,
+            };
+            to_string(&with_source, format)
+        } else {
+            to_string(self, format)
+        }
+    }
+}
+
+/// An include directory or define of a compile command, found by `merged_options`.
+struct MergedOption {
+    /// The option, e.g. `-isystem`.
+    option: &'static str,
+    /// The directory or the define, without the option.
+    value: String,
+    /// The position of its arguments.
+    range: std::ops::Range<usize>,
+}
+
+impl MergedOption {
+    /// Returns the option as written in the arguments.
+    fn text(&self, arguments: &[String]) -> String {
+        arguments[self.range.clone()].join(" ")
+    }
+}
+
+/// Extracts the values of a compile command attribute that a filter matches against.
+type EntryValues<'a> = Box<dyn Fn(&CompileCommand) -> Vec<String> + 'a>;
+
+/// An allow-list and a deny-list of regexes applied to one attribute of a compile command.
+struct EntryFilter<'a> {
+    only: Vec<Regex>,
+    ignore: Vec<Regex>,
+    values: EntryValues<'a>,
+}
+
+impl<'a> EntryFilter<'a> {
+    fn new(
+        only: &[String],
+        ignore: &[String],
+        values: impl Fn(&CompileCommand) -> Vec<String> + 'a,
+    ) -> Self {
+        let to_regex = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|x| Regex::new(x).unwrap())
+                .collect::<Vec<_>>()
+        };
+        Self {
+            only: to_regex(only),
+            ignore: to_regex(ignore),
+            values: Box::new(values),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.only.is_empty() && self.ignore.is_empty()
+    }
+
+    /// Returns `true` if any deny-list regex matches any value of the entry.
+    fn is_excluded(&self, cc: &CompileCommand) -> bool {
+        if self.ignore.is_empty() {
+            return false;
+        }
+        let values = (self.values)(cc);
+        self.ignore
+            .iter()
+            .any(|regex| values.iter().any(|v| regex.is_match(v)))
+    }
+
+    /// Returns `true` if the allow-list is empty or any of its regexes matches a value.
+    fn is_included(&self, cc: &CompileCommand) -> bool {
+        if self.only.is_empty() {
+            return true;
+        }
+        let values = (self.values)(cc);
+        self.only
+            .iter()
+            .any(|regex| values.iter().any(|v| regex.is_match(v)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postprocess_config::PathRule;
+
+    fn entry(file: &str, command: &str) -> CompileCommand {
+        CompileCommand {
+            command: command.to_string(),
+            directory: "/work".to_string(),
+            file: file.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn retain_first_keeps_build_variants_apart_by_output() {
+        let mut a_debug = entry("a.cc", "g++ -O0 -c a.cc");
+        a_debug.output = "debug/a.o".to_string();
+        let mut a_release = entry("/work/a.cc", "g++ -O2 -c a.cc");
+        a_release.output = "release/a.o".to_string();
+        let compile_commands = vec![a_debug.clone(), a_release, a_debug];
+
+        let by_file =
+            CompileCommand::deduplicate_with_retain_first(compile_commands.clone(), DedupKey::File);
+        assert_eq!(by_file.len(), 1);
+        let by_output = CompileCommand::deduplicate_with_retain_first(
+            compile_commands,
+            DedupKey::FileAndOutput,
+        );
+        assert_eq!(by_output.len(), 2);
+    }
+
+    #[test]
+    fn merge_separated_include_and_define_options() {
+        let compile_commands = vec![
+            entry("a.cc", "g++ -isystem /sys/a -I /inc/a -D A=1 -c a.cc"),
+            entry(
+                "a.cc",
+                "g++ -isystem /sys/b -isystem/sys/a -iquote /q -I/inc/a -DA=2 -D B -c a.cc",
+            ),
+        ];
+        let merged = CompileCommand::deduplicate_with_merge(compile_commands, DedupKey::File, &[]);
+        assert_eq!(
+            merged[0].arguments.join(" "),
+            "g++ -iquote /q -isystem /sys/a -isystem /sys/b -I /inc/a -D A=1 -D B -c a.cc"
+        );
+    }
+
+    #[test]
+    fn postprocess_keeps_the_merged_separated_options() {
+        let compile_commands = vec![
+            entry("a.cc", "g++ -isystem /sys/a -I /inc/a -D A=1 -c a.cc"),
+            entry("a.cc", "g++ -isystem /sys/b -I /inc/b -D B -D A=1 -c a.cc"),
+        ];
+        let mut merged =
+            CompileCommand::deduplicate_with_merge(compile_commands, DedupKey::File, &[]);
+        merged[0].postprocess(&Some(PostProcessConfig::default()), None, &[]);
+        assert_eq!(
+            merged[0].command,
+            "g++ -isystem /sys/a -isystem /sys/b -I /inc/a -I /inc/b -D A=1 -D B -c a.cc"
+        );
+    }
+
+    #[test]
+    fn merge_skips_configured_compiler_wrappers() {
+        let compile_commands = vec![
+            entry("a.cc", "launcher g++ -c a.cc"),
+            entry("a.cc", "launcher g++ -I/inc -c a.cc"),
+        ];
+        let wrappers = vec!["launcher".to_string()];
+        let merged =
+            CompileCommand::deduplicate_with_merge(compile_commands, DedupKey::File, &wrappers);
+        assert_eq!(merged[0].arguments.join(" "), "launcher g++ -I/inc -c a.cc");
+    }
+
+    #[test]
+    fn insert_after_the_compiler_when_the_wrapper_is_removed() {
+        let mut cc = entry("a.cc", "ccache g++ -O2 -c a.cc");
+        let pp_config = PostProcessConfig {
+            remove: vec!["^ccache$".to_string()],
+            insert: vec!["-DX".to_string()],
+            ..Default::default()
+        };
+        cc.postprocess(&Some(pp_config), None, &[]);
+        assert_eq!(cc.command, "g++ -DX -O2 -c a.cc");
+    }
+
+    #[test]
+    fn remove_an_option_together_with_its_separate_value() {
+        let mut cc = entry("a.cc", "g++ -isystem /x -isystem/y -I/x -c a.cc");
+        let pp_config = PostProcessConfig {
+            remove: vec!["^-isystem /x$".to_string()],
+            ..Default::default()
+        };
+        cc.postprocess(&Some(pp_config), None, &[]);
+        assert_eq!(cc.command, "g++ -isystem/y -I/x -c a.cc");
+    }
+
+    #[test]
+    fn a_regex_without_a_space_only_matches_single_arguments() {
+        let mut cc = entry("a.cc", "g++ -isystem /x -I/y -c a.cc");
+        let pp_config = PostProcessConfig {
+            remove: vec!["^-isystem".to_string(), "^-I".to_string()],
+            ..Default::default()
+        };
+        cc.postprocess(&Some(pp_config), None, &[]);
+        assert_eq!(cc.command, "g++ /x -c a.cc");
+    }
+
+    #[test]
+    fn path_rules_apply_to_the_matching_files_only() {
+        let pp_config = PostProcessConfig {
+            path_rules: vec![PathRule {
+                path_match: "^/work/kpar/".to_string(),
+                remove: vec!["^-O2$".to_string()],
+                insert: vec!["-DKPAR".to_string()],
+            }],
+            ..Default::default()
+        };
+        let path_rules = pp_config.compile_path_rules();
+        let pp_config = Some(pp_config);
+        let mut kpar = entry("kpar/a.cc", "g++ -O2 -c kpar/a.cc");
+        let mut other = entry("b.cc", "g++ -O2 -c b.cc");
+        kpar.postprocess(&pp_config, None, &path_rules);
+        other.postprocess(&pp_config, None, &path_rules);
+        assert_eq!(kpar.command, "g++ -DKPAR -c kpar/a.cc");
+        assert_eq!(other.command, "g++ -O2 -c b.cc");
     }
 }
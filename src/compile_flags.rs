@@ -0,0 +1,174 @@
+use crate::compile_commands::CompileCommand;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+/// Computes the flags shared by the translation units of each directory.
+///
+/// The compiler, the source file, the output and dependency-file options are left out.
+/// An option is compared together with its value, so `-isystem /a` is shared only when
+/// every translation unit has `-isystem /a`.
+/// Only directories equal to or below one of `roots` are kept, unless `roots` is empty.
+/// A relative root is resolved against the current directory, and a root matching no entry
+/// is reported as a warning.
+///
+/// # Arguments
+///
+/// * `compile_commands` - The postprocessed compile commands.
+/// * `roots` - The directories to generate flags for; empty means every directory.
+/// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+///
+/// # Examples
+///
+/// ```
+/// use ccj_postprocess::compile_commands::CompileCommand;
+/// use ccj_postprocess::compile_flags;
+///
+/// let entry = |file: &str, arguments: &[&str]| CompileCommand {
+///     command: "".to_string(),
+///     arguments: arguments.iter().map(|x| x.to_string()).collect(),
+///     directory: "/work/src".to_string(),
+///     file: file.to_string(),
+///     output: "".to_string(),
+///     ..Default::default()
+/// };
+/// let compile_commands = vec![
+///     entry("a.cc", &["g++", "-I/inc", "-DA", "-c", "a.cc", "-o", "a.o"]),
+///     entry("b.cc", &["g++", "-I/inc", "-DB", "-c", "b.cc", "-o", "b.o"]),
+/// ];
+/// let flags = compile_flags::shared_flags_by_directory(&compile_commands, &[], &[]);
+/// assert_eq!(flags["/work/src"], vec!["-I/inc"]);
+/// ```
+pub fn shared_flags_by_directory(
+    compile_commands: &[CompileCommand],
+    roots: &[String],
+    extra_wrappers: &[String],
+) -> BTreeMap<String, Vec<String>> {
+    let roots = roots
+        .iter()
+        .map(|x| {
+            let root = std::path::absolute(x)
+                .unwrap_or_else(|_| panic!("[Error] invalid compile flags root {}", x));
+            CompileCommand::normalize_path(&root)
+        })
+        .collect::<Vec<_>>();
+    let mut matched = vec![false; roots.len()];
+    let mut shared: BTreeMap<String, Vec<Vec<String>>> = BTreeMap::new();
+    for cc in compile_commands {
+        let source_path = cc.source_path();
+        let directory = Path::new(&source_path)
+            .parent()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !roots.is_empty() {
+            let root = roots
+                .iter()
+                .position(|root| Path::new(&directory).starts_with(root));
+            match root {
+                Some(index) => matched[index] = true,
+                None => continue,
+            }
+        }
+        let flags = cc.compile_flags(extra_wrappers);
+        let options = CompileCommand::option_groups(&flags);
+        match shared.get_mut(&directory) {
+            Some(common) => {
+                let options = options.into_iter().collect::<HashSet<_>>();
+                common.retain(|x| options.contains(x.as_slice()));
+            }
+            None => {
+                shared.insert(directory, options.into_iter().map(|x| x.to_vec()).collect());
+            }
+        }
+    }
+    for (root, _) in roots.iter().zip(matched).filter(|(_, matched)| !matched) {
+        eprintln!("[Warning] the compile flags root {} matches no entry", root);
+    }
+    shared
+        .into_iter()
+        .map(|(directory, options)| (directory, options.concat()))
+        .collect()
+}
+
+/// Writes a `compile_flags.txt` with the shared flags into each directory.
+///
+/// # Arguments
+///
+/// * `shared_flags` - The flags of each directory, from `shared_flags_by_directory`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ccj_postprocess::compile_flags;
+///
+/// let compile_commands = vec![];
+/// let flags = compile_flags::shared_flags_by_directory(&compile_commands, &[], &[]);
+/// compile_flags::write_compile_flags(&flags);
+/// ```
+pub fn write_compile_flags(shared_flags: &BTreeMap<String, Vec<String>>) {
+    for (directory, flags) in shared_flags {
+        let path = Path::new(directory).join("compile_flags.txt");
+        let mut context = flags.join("\n");
+        context.push('\n');
+        std::fs::write(&path, context)
+            .unwrap_or_else(|_| panic!("cannot write the file {:?}", path));
+        println!("{}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(file: &str, arguments: &[&str]) -> CompileCommand {
+        CompileCommand {
+            command: "".to_string(),
+            arguments: arguments.iter().map(|x| x.to_string()).collect(),
+            directory: "/work".to_string(),
+            file: file.to_string(),
+            output: "".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn options_are_shared_together_with_their_values() {
+        let compile_commands = vec![
+            entry(
+                "a.cc",
+                &[
+                    "g++", "-isystem", "/a", "-include", "pch.h", "-D", "X", "a.cc",
+                ],
+            ),
+            entry(
+                "b.cc",
+                &[
+                    "g++", "-isystem", "/b", "-include", "pch.h", "-D", "Y", "b.cc",
+                ],
+            ),
+        ];
+        let flags = shared_flags_by_directory(&compile_commands, &[], &[]);
+        assert_eq!(flags["/work"], vec!["-include", "pch.h"]);
+    }
+
+    #[test]
+    fn configured_wrappers_are_not_shared_flags() {
+        let compile_commands = vec![
+            entry("a.cc", &["launcher", "g++", "-O2", "a.cc"]),
+            entry("b.cc", &["launcher", "g++", "-O2", "b.cc"]),
+        ];
+        let flags = shared_flags_by_directory(&compile_commands, &[], &["launcher".to_string()]);
+        assert_eq!(flags["/work"], vec!["-O2"]);
+    }
+
+    #[test]
+    fn relative_roots_are_resolved_against_the_current_directory() {
+        let current_directory = std::env::current_dir().unwrap();
+        let compile_commands = vec![CompileCommand {
+            directory: current_directory.to_string_lossy().into_owned(),
+            ..entry("src/a.cc", &["g++", "-O2", "src/a.cc"])
+        }];
+        let flags = shared_flags_by_directory(&compile_commands, &["src".to_string()], &[]);
+        let src = CompileCommand::normalize_path(&current_directory.join("src"));
+        assert_eq!(flags[&src], vec!["-O2"]);
+    }
+}
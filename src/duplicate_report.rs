@@ -0,0 +1,160 @@
+use crate::compile_commands::{CompileCommand, EntrySource};
+use serde::Serialize;
+
+/// Flag prefixes compared between duplicate entries.
+const COMPARED_FLAG_PREFIXES: [&str; 6] = ["-I", "-isystem", "-iquote", "-D", "-O", "-std="];
+
+/// A file with more than one compile command across all inputs.
+#[derive(Serialize, Debug)]
+pub struct DuplicateReport {
+    /// The normalized source path.
+    pub file: String,
+    /// The entries of the file; the flags of each are diffed against the first entry.
+    pub entries: Vec<DuplicateEntry>,
+}
+
+/// One entry of a duplicated file.
+#[derive(Serialize, Debug)]
+pub struct DuplicateEntry {
+    /// The compile database and the position in it that the entry was read from.
+    pub source: EntrySource,
+    /// The compared flags that the first entry does not have.
+    pub added: Vec<String>,
+    /// The compared flags of the first entry that this entry does not have.
+    pub removed: Vec<String>,
+}
+
+impl DuplicateReport {
+    /// Collects the files with more than one compile command.
+    ///
+    /// The `-I`, `-isystem`, `-iquote`, `-D`, `-O` and `-std` flags of every entry are diffed
+    /// against the first entry of the file. An option with a separate value is compared glued to
+    /// it, so `-I /inc` and `-I/inc` are the same flag.
+    ///
+    /// # Arguments
+    ///
+    /// * `compile_commands` - The compile commands of all inputs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    /// use ccj_postprocess::duplicate_report::DuplicateReport;
+    ///
+    /// let entry = |command: &str| CompileCommand {
+    ///     command: command.to_string(),
+    ///     arguments: vec![],
+    ///     directory: "/work".to_string(),
+    ///     file: "a.cc".to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let compile_commands = vec![entry("g++ -O2 -I/inc -c a.cc"), entry("g++ -O1 -I /inc -D X -c a.cc")];
+    /// let reports = DuplicateReport::collect(&compile_commands);
+    /// assert_eq!(reports.len(), 1);
+    /// assert_eq!(reports[0].entries[1].added, vec!["-O1", "-DX"]);
+    /// assert_eq!(reports[0].entries[1].removed, vec!["-O2"]);
+    /// ```
+    pub fn collect(compile_commands: &[CompileCommand]) -> Vec<DuplicateReport> {
+        let mut files: Vec<(String, Vec<&CompileCommand>)> = Vec::new();
+        let mut position = std::collections::HashMap::<String, usize>::new();
+        for cc in compile_commands {
+            let key = cc.source_path();
+            match position.get(&key) {
+                Some(&index) => files[index].1.push(cc),
+                None => {
+                    position.insert(key.clone(), files.len());
+                    files.push((key, vec![cc]));
+                }
+            }
+        }
+
+        files
+            .into_iter()
+            .filter(|(_, entries)| entries.len() > 1)
+            .map(|(file, entries)| {
+                let first = Self::compared_flags(entries[0]);
+                let entries = entries
+                    .iter()
+                    .map(|cc| {
+                        let flags = Self::compared_flags(cc);
+                        DuplicateEntry {
+                            source: cc.source.clone(),
+                            added: flags
+                                .iter()
+                                .filter(|x| !first.contains(x))
+                                .cloned()
+                                .collect(),
+                            removed: first
+                                .iter()
+                                .filter(|x| !flags.contains(x))
+                                .cloned()
+                                .collect(),
+                        }
+                    })
+                    .collect();
+                DuplicateReport { file, entries }
+            })
+            .collect()
+    }
+
+    /// Prints the reports in a human-readable text format.
+    ///
+    /// # Arguments
+    ///
+    /// * `reports` - The duplicate reports.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::duplicate_report::DuplicateReport;
+    ///
+    /// DuplicateReport::dump_text(&[]);
+    /// ```
+    pub fn dump_text(reports: &[DuplicateReport]) {
+        for report in reports {
+            println!("{} ({} entries)", report.file, report.entries.len());
+            for entry in &report.entries {
+                println!("  {}", entry.source);
+                for flag in &entry.added {
+                    println!("    + {}", flag);
+                }
+                for flag in &entry.removed {
+                    println!("    - {}", flag);
+                }
+            }
+        }
+    }
+
+    /// Prints the reports as a JSON array.
+    ///
+    /// # Arguments
+    ///
+    /// * `reports` - The duplicate reports.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::duplicate_report::DuplicateReport;
+    ///
+    /// DuplicateReport::dump_json(&[]);
+    /// ```
+    pub fn dump_json(reports: &[DuplicateReport]) {
+        println!("{}", serde_json::to_string_pretty(reports).unwrap());
+    }
+
+    /// Returns the flags of a compile command that are compared between duplicates.
+    fn compared_flags(cc: &CompileCommand) -> Vec<String> {
+        let is_compared = |x: &str| COMPARED_FLAG_PREFIXES.iter().any(|p| x.starts_with(p));
+        let arguments = if cc.arguments.is_empty() {
+            cc.command.split(' ').map(|x| x.to_string()).collect()
+        } else {
+            cc.arguments.clone()
+        };
+        CompileCommand::option_groups(&arguments)
+            .into_iter()
+            .filter(|group| is_compared(&group[0]))
+            .map(|group| group.concat())
+            .collect()
+    }
+}
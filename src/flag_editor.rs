@@ -0,0 +1,287 @@
+use crate::compile_commands::CompileCommand;
+use crate::skim_utility;
+use serde_json::Value;
+use std::path::Path;
+
+/// The first flag offered for removal, picked to remove none, as skim accepts the highlighted
+/// flag when none is marked.
+const REMOVE_NOTHING: &str = "(remove nothing)";
+
+/// The flags a user marked for removal and typed for insertion.
+#[derive(Debug, Default)]
+pub struct FlagEdits {
+    /// Flags of the translation unit to remove.
+    pub remove: Vec<String>,
+    /// New flags to insert after the compiler.
+    pub insert: Vec<String>,
+}
+
+impl FlagEdits {
+    /// Adds the edits to a raw postprocess config.
+    ///
+    /// A removed flag becomes the exact-match regex `^flag$`. Without a directory the edits go
+    /// to the global `remove` and `insert` lists; with one they go to the path rule matching
+    /// the files below it, which is created when missing. Entries already present are not
+    /// added twice, and the other keys of the config are kept as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The postprocess config as JSON.
+    /// * `directory` - The directory the edits are scoped to, or `None` for global edits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::flag_editor::FlagEdits;
+    ///
+    /// let mut config = serde_json::from_str(r#"{"remove": ["^-Werror$"]}"#).unwrap();
+    /// let edits = FlagEdits {
+    ///     remove: vec!["-Werror".to_string(), "-O2".to_string()],
+    ///     insert: vec!["-O0".to_string()],
+    /// };
+    /// edits.save_into(&mut config, None);
+    /// edits.save_into(&mut config, Some("/work/kpar"));
+    /// assert_eq!(
+    ///     serde_json::to_string(&config).unwrap(),
+    ///     r#"{"remove":["^-Werror$","^-O2$"],"insert":["-O0"],"path_rules":[{"path_match":"^/work/kpar/","remove":["^-Werror$","^-O2$"],"insert":["-O0"]}]}"#
+    /// );
+    /// ```
+    pub fn save_into(&self, config: &mut Value, directory: Option<&str>) {
+        let target = match directory {
+            None => config,
+            Some(directory) => {
+                let path_match = format!("^{}/", escape_flag(directory.trim_end_matches('/')));
+                let rules = config
+                    .as_object_mut()
+                    .expect("[Error] the postprocess config is not a JSON object")
+                    .entry("path_rules")
+                    .or_insert_with(|| Value::Array(Vec::new()))
+                    .as_array_mut()
+                    .expect("[Error] path_rules of the postprocess config is not an array");
+                let position = rules
+                    .iter()
+                    .position(|x| x["path_match"].as_str() == Some(path_match.as_str()));
+                let position = position.unwrap_or_else(|| {
+                    rules.push(serde_json::json!({ "path_match": path_match }));
+                    rules.len() - 1
+                });
+                &mut rules[position]
+            }
+        };
+        let remove = self
+            .remove
+            .iter()
+            .map(|x| format!("^{}$", escape_flag(x)))
+            .collect::<Vec<_>>();
+        append_unique(target, "remove", &remove);
+        append_unique(target, "insert", &self.insert);
+    }
+}
+
+/// Lets the user edit the flags of a translation unit and saves them into the config file.
+///
+/// The user picks a translation unit, marks the flags to remove, types the flags to insert
+/// and chooses whether the edits apply to every file or only to the directory of the
+/// picked file. An option with a separate value, such as `-isystem /x`, is offered and
+/// removed as one flag. The edits go to the active profile, the one given or else the
+/// `default_profile` of the config, or to the top level without one. Cancelling any step
+/// leaves the config file untouched.
+///
+/// # Arguments
+///
+/// * `compile_commands` - The postprocessed compile commands.
+/// * `config_file` - The path of the postprocess config file to update.
+/// * `profile` - The profile given on the command line, if any.
+/// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ccj_postprocess::compile_commands::CompileCommand;
+/// use ccj_postprocess::flag_editor;
+///
+/// let compile_commands = CompileCommand::parse("compile_commands.json");
+/// flag_editor::edit_flags(&compile_commands, "postprocess_config.json", None, &[]);
+/// ```
+pub fn edit_flags(
+    compile_commands: &[CompileCommand],
+    config_file: &str,
+    profile: Option<&str>,
+    extra_wrappers: &[String],
+) {
+    let Some(cc) = skim_utility::pick_one_file(compile_commands, extra_wrappers) else {
+        return;
+    };
+    let flags = cc.compile_flags(extra_wrappers);
+    let offered = [REMOVE_NOTHING.to_string()]
+        .into_iter()
+        .chain(
+            CompileCommand::option_groups(&flags)
+                .into_iter()
+                .map(|x| x.join(" ")),
+        )
+        .collect::<Vec<_>>();
+    let Some(remove) = skim_utility::pick_strings(
+        &offered,
+        "Flags to remove (Tab to mark, Enter to accept): ",
+        true,
+    ) else {
+        return;
+    };
+    let remove = remove
+        .into_iter()
+        .filter(|x| x != REMOVE_NOTHING)
+        .collect::<Vec<_>>();
+    let Some(insert) = skim_utility::read_line("Flags to insert (space-separated): ") else {
+        return;
+    };
+    let edits = FlagEdits {
+        remove,
+        insert: insert.split_whitespace().map(|x| x.to_string()).collect(),
+    };
+    if edits.remove.is_empty() && edits.insert.is_empty() {
+        eprintln!("No flags changed.");
+        return;
+    }
+
+    let source_path = cc.source_path();
+    let directory = Path::new(&source_path)
+        .parent()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let scopes = vec![
+        "every file".to_string(),
+        format!("files below {}", directory),
+    ];
+    let Some(scope) = skim_utility::pick_strings(&scopes, "Apply to: ", false) else {
+        return;
+    };
+    let directory = (scope.first() != Some(&scopes[0])).then_some(directory.as_str());
+
+    let context = std::fs::read_to_string(config_file)
+        .unwrap_or_else(|_| panic!("cannot open the file {}", config_file));
+    let mut config: Value = serde_json::from_str(&context)
+        .unwrap_or_else(|_| panic!("[Error] json file {} parse fail!", config_file));
+    let profile = profile
+        .map(|x| x.to_string())
+        .or_else(|| config["default_profile"].as_str().map(|x| x.to_string()));
+    edits.save_into(edited_object(&mut config, profile.as_deref()), directory);
+    let mut context = serde_json::to_string_pretty(&config).unwrap();
+    context.push('\n');
+    std::fs::write(config_file, context)
+        .unwrap_or_else(|_| panic!("cannot write the file {}", config_file));
+    match profile {
+        Some(profile) => eprintln!("Saved into the profile {} of {}", profile, config_file),
+        None => eprintln!("Saved into {}", config_file),
+    }
+}
+
+/// Returns the object of a raw postprocess config the edits go to: the profile, if any, or
+/// else the top level.
+fn edited_object<'a>(config: &'a mut Value, profile: Option<&str>) -> &'a mut Value {
+    match profile {
+        Some(profile) => config
+            .get_mut("profiles")
+            .and_then(|x| x.get_mut(profile))
+            .unwrap_or_else(|| {
+                panic!(
+                    "[Error] profile {} is not defined in the postprocess config",
+                    profile
+                )
+            }),
+        None => config,
+    }
+}
+
+/// Appends the values missing from a string array of a JSON object, creating the array.
+///
+/// An option with a separate value is compared and appended together with its value, so
+/// `-isystem /b` is added next to an existing `-isystem /a`.
+fn append_unique(object: &mut Value, key: &str, values: &[String]) {
+    if values.is_empty() {
+        return;
+    }
+    let array = object
+        .as_object_mut()
+        .expect("[Error] the postprocess config is not a JSON object")
+        .entry(key)
+        .or_insert_with(|| Value::Array(Vec::new()))
+        .as_array_mut()
+        .unwrap_or_else(|| panic!("[Error] {} of the postprocess config is not an array", key));
+    let existing = array
+        .iter()
+        .filter_map(|x| x.as_str().map(|x| x.to_string()))
+        .collect::<Vec<_>>();
+    let mut groups = CompileCommand::option_groups(&existing)
+        .into_iter()
+        .map(|x| x.to_vec())
+        .collect::<Vec<_>>();
+    for group in CompileCommand::option_groups(values) {
+        if !groups.iter().any(|x| x == group) {
+            array.extend(group.iter().cloned().map(Value::String));
+            groups.push(group.to_vec());
+        }
+    }
+}
+
+/// Escapes a flag or path for a regex, keeping the dashes readable as they are literal anyway.
+fn escape_flag(text: &str) -> String {
+    regex::escape(text).replace("\\-", "-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_go_to_the_profile() {
+        let mut config = serde_json::json!({
+            "remove": ["^-g$"],
+            "profiles": { "clangd": { "insert": ["-DX"] } }
+        });
+        let edits = FlagEdits {
+            remove: vec!["-isystem /x".to_string()],
+            insert: vec![],
+        };
+        edits.save_into(edited_object(&mut config, Some("clangd")), None);
+        assert_eq!(
+            config,
+            serde_json::json!({
+                "remove": ["^-g$"],
+                "profiles": { "clangd": { "insert": ["-DX"], "remove": ["^-isystem /x$"] } }
+            })
+        );
+    }
+
+    #[test]
+    fn inserted_options_are_deduplicated_with_their_values() {
+        let mut config = serde_json::json!({ "insert": ["-isystem", "/a"] });
+        let edits = FlagEdits {
+            remove: vec![],
+            insert: ["-isystem", "/b", "-isystem", "/a", "-DX", "-DX"]
+                .map(|x| x.to_string())
+                .to_vec(),
+        };
+        edits.save_into(&mut config, None);
+        assert_eq!(
+            config,
+            serde_json::json!({ "insert": ["-isystem", "/a", "-isystem", "/b", "-DX"] })
+        );
+    }
+
+    #[test]
+    fn edits_go_to_the_top_level_without_a_profile() {
+        let mut config = serde_json::json!({ "profiles": { "clangd": {} } });
+        assert_eq!(
+            *edited_object(&mut config, None),
+            serde_json::json!({ "profiles": { "clangd": {} } })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "profile tidy is not defined")]
+    fn an_unknown_profile_is_refused() {
+        let mut config = serde_json::json!({ "profiles": { "clangd": {} } });
+        edited_object(&mut config, Some("tidy"));
+    }
+}
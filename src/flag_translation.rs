@@ -0,0 +1,179 @@
+use crate::postprocess_config::PostProcessConfig;
+use regex::Regex;
+
+/// GCC-only options that clang rejects or silently misinterprets, dropped after a
+/// compiler substitution.
+const BUILTIN_DROP_FLAGS: [&str; 16] = [
+    "^-fno-canonical-system-headers$",
+    "^-fconserve-stack$",
+    "^-mabi=.*$",
+    "^-mno-abicalls$",
+    "^-fno-var-tracking-assignments$",
+    "^-fvar-tracking(-assignments)?$",
+    "^-fno-ipa-.*$",
+    "^-fipa-.*$",
+    "^-fno-tree-.*$",
+    "^-ftree-.*$",
+    "^-fno-lifetime-dse$",
+    "^-fno-gnu-unique$",
+    "^-fno-aggressive-loop-optimizations$",
+    "^-finline-limit=.*$",
+    "^-fno-partial-inlining$",
+    "^-flto-partition=.*$",
+];
+
+/// GCC options with a clang spelling, as `regex,replacement` pairs.
+const BUILTIN_TRANSLATE_FLAGS: [&str; 3] = [
+    "^-fmax-errors=(.*)$,-ferror-limit=$1",
+    "^-fdiagnostics-color$,-fcolor-diagnostics",
+    "^-fno-diagnostics-color$,-fno-color-diagnostics",
+];
+
+/// Substitutes the compiler and drops or rewrites GCC-only options for a clang driver.
+///
+/// The built-in table can be extended with the `drop_flags` and `translate_flags` of the
+/// postprocess config. Every regex is compiled once, so one translator serves all entries.
+pub struct FlagTranslator {
+    compiler_map: Vec<(Regex, String)>,
+    drop: Vec<Regex>,
+    translate: Vec<(Regex, String)>,
+}
+
+impl FlagTranslator {
+    /// Creates a translator from the built-in table and extra rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `extra_drop` - Regexes of additional options to drop.
+    /// * `extra_translate` - Additional `regex,replacement` pairs; the replacement may
+    ///   refer to capture groups like `$1`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::flag_translation::FlagTranslator;
+    ///
+    /// let translator = FlagTranslator::new(&[], &["^-Wno-psabi$,-Wno-unknown-warning-option".to_string()]);
+    /// ```
+    pub fn new(extra_drop: &[String], extra_translate: &[String]) -> Self {
+        let drop = BUILTIN_DROP_FLAGS
+            .iter()
+            .map(|x| x.to_string())
+            .chain(extra_drop.iter().cloned())
+            .map(|x| Regex::new(&x).unwrap())
+            .collect();
+        let translate = BUILTIN_TRANSLATE_FLAGS
+            .iter()
+            .map(|x| x.to_string())
+            .chain(extra_translate.iter().cloned())
+            .filter_map(|x| {
+                let (from, to) = x.split_once(',')?;
+                Some((Regex::new(from).unwrap(), to.to_string()))
+            })
+            .collect();
+        Self {
+            compiler_map: Vec::new(),
+            drop,
+            translate,
+        }
+    }
+
+    /// Creates a translator from the `compiler_map`, `drop_flags` and `translate_flags` of the
+    /// postprocess config.
+    ///
+    /// # Arguments
+    ///
+    /// * `ppc` - The postprocess config.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::flag_translation::FlagTranslator;
+    /// use ccj_postprocess::postprocess_config::PostProcessConfig;
+    ///
+    /// let ppc = PostProcessConfig {
+    ///     compiler_map: vec!["^/depot/qsc/.*/GCC/bin/g\\+\\+$,clang++".to_string()],
+    ///     ..Default::default()
+    /// };
+    /// let translator = FlagTranslator::from_config(&ppc);
+    /// let mut args = ["/depot/qsc/QSCT/GCC/bin/g++", "-c"].map(String::from).to_vec();
+    /// assert!(translator.substitute_compiler(&mut args, 0));
+    /// assert_eq!(args, vec!["clang++", "-c"]);
+    /// ```
+    pub fn from_config(ppc: &PostProcessConfig) -> Self {
+        let compiler_map = ppc
+            .compiler_map
+            .iter()
+            .filter_map(|x| {
+                let (from, to) = x.split_once(',')?;
+                Some((Regex::new(from).unwrap(), to.to_string()))
+            })
+            .collect();
+        Self {
+            compiler_map,
+            ..Self::new(&ppc.drop_flags, &ppc.translate_flags)
+        }
+    }
+
+    /// Replaces the compiler with the target of the first matching compiler mapping.
+    ///
+    /// # Arguments
+    ///
+    /// * `arguments` - The command-line arguments.
+    /// * `compiler_index` - The index of the compiler, past any compiler wrappers.
+    ///
+    /// # Returns
+    ///
+    /// - `bool` - `true` if the compiler was substituted, otherwise `false`.
+    pub fn substitute_compiler(&self, arguments: &mut [String], compiler_index: usize) -> bool {
+        let Some(compiler) = arguments.get_mut(compiler_index) else {
+            return false;
+        };
+        match self
+            .compiler_map
+            .iter()
+            .find(|(regex, _)| regex.is_match(compiler))
+        {
+            Some((_, to)) => {
+                *compiler = to.clone();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drops and translates the options following the compiler.
+    ///
+    /// # Arguments
+    ///
+    /// * `arguments` - The command-line arguments.
+    /// * `compiler_index` - The index of the compiler; it and everything before it are untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::flag_translation::FlagTranslator;
+    ///
+    /// let translator = FlagTranslator::new(&[], &[]);
+    /// let mut args = ["clang++", "-fconserve-stack", "-fmax-errors=5", "-c", "a.cc"].map(String::from).to_vec();
+    /// translator.translate(&mut args, 0);
+    /// assert_eq!(args, vec!["clang++", "-ferror-limit=5", "-c", "a.cc"]);
+    /// ```
+    pub fn translate(&self, arguments: &mut Vec<String>, compiler_index: usize) {
+        if arguments.len() <= compiler_index + 1 {
+            return;
+        }
+        let mut options = arguments.split_off(compiler_index + 1);
+        options.retain(|x| self.drop.iter().all(|regex| !regex.is_match(x)));
+        for option in &mut options {
+            if let Some((regex, to)) = self
+                .translate
+                .iter()
+                .find(|(regex, _)| regex.is_match(option))
+            {
+                *option = regex.replace(option, to.as_str()).into_owned();
+            }
+        }
+        arguments.append(&mut options);
+    }
+}
@@ -0,0 +1,31 @@
+/// Hashes a list of strings with 64-bit FNV-1a, joined by newlines.
+///
+/// The hash is stable across runs and platforms, so it can name files that later runs find
+/// again.
+///
+/// # Arguments
+///
+/// * `parts` - The strings to hash, in order.
+///
+/// # Returns
+///
+/// - `u64` - The FNV-1a hash of the joined strings.
+///
+/// # Examples
+///
+/// ```
+/// use ccj_postprocess::hash_utility;
+///
+/// let hash = hash_utility::fnv1a(&["-I/inc".to_string(), "-DX".to_string()]);
+/// assert_eq!(hash, hash_utility::fnv1a(&["-I/inc".to_string(), "-DX".to_string()]));
+/// assert_ne!(hash, hash_utility::fnv1a(&["-DX".to_string(), "-I/inc".to_string()]));
+/// assert_eq!(hash_utility::fnv1a(&[]), 0xcbf29ce484222325);
+/// ```
+pub fn fnv1a(parts: &[String]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in parts.join("\n").bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
@@ -0,0 +1,357 @@
+use crate::compile_commands::CompileCommand;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+/// Extensions of the header files picked up from the include directories.
+const HEADER_EXTENSIONS: [&str; 5] = ["h", "hh", "hpp", "hxx", "h++"];
+
+/// Extensions of the C translation units; the headers they lend flags to are C headers.
+const C_SOURCE_EXTENSIONS: [&str; 1] = ["c"];
+
+/// Options naming the include directories scanned for headers, glued to their value or not.
+/// The `-isystem` directories hold system and third-party headers, which are not synthesized.
+const SCANNED_INCLUDE_OPTIONS: [&str; 2] = ["-I", "-iquote"];
+
+/// The deepest subdirectory of an include directory that is scanned for headers.
+const MAX_SCAN_DEPTH: usize = 8;
+
+/// The most headers collected from the include directories.
+const MAX_HEADERS: usize = 100_000;
+
+/// Creates a compile command for every header in the include directories of the database.
+///
+/// The `-I` and `-iquote` directories, but not the `-isystem` ones, are scanned recursively,
+/// down to 8 levels and 100000 headers, and headers that already have an entry are skipped.
+/// Each header borrows the flags of the best-matching translation unit: one in the same
+/// directory first, then the one sharing the longest path prefix with the header; among
+/// equally ranked units, and when no unit shares any directory with the header, one that
+/// `#include`s the header wins.
+/// The compiler is told the language with `-x c++-header`, or `-x c-header` for a `.h` file
+/// borrowing from a C translation unit.
+///
+/// # Arguments
+///
+/// * `compile_commands` - The postprocessed compile commands.
+/// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+///
+/// # Returns
+///
+/// - `Vec<CompileCommand>` - The synthesized header entries, sorted by path.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ccj_postprocess::compile_commands::CompileCommand;
+/// use ccj_postprocess::header_entries;
+///
+/// let mut compile_commands = CompileCommand::parse("compile_commands.json");
+/// let headers = header_entries::synthesize_header_entries(&compile_commands, &[]);
+/// compile_commands.extend(headers);
+/// ```
+pub fn synthesize_header_entries(
+    compile_commands: &[CompileCommand],
+    extra_wrappers: &[String],
+) -> Vec<CompileCommand> {
+    let known_files = compile_commands
+        .iter()
+        .map(|x| x.source_path())
+        .collect::<HashSet<_>>();
+    let include_directories = compile_commands
+        .iter()
+        .flat_map(include_directories)
+        .collect::<BTreeSet<_>>();
+    let mut headers = BTreeSet::new();
+    let mut scanned = HashSet::new();
+    for directory in &include_directories {
+        collect_headers(Path::new(directory), 0, &mut scanned, &mut headers);
+    }
+    if headers.len() >= MAX_HEADERS {
+        eprintln!(
+            "[Warning] stopped scanning the include directories after {} headers",
+            MAX_HEADERS
+        );
+    }
+
+    // every source is read once, however many headers there are
+    let unit_directories = compile_commands
+        .iter()
+        .map(|cc| {
+            Path::new(&cc.source_path())
+                .parent()
+                .map(|x| x.to_path_buf())
+        })
+        .collect::<Vec<_>>();
+    let mut include_index = IncludeIndex::default();
+    for (unit, cc) in compile_commands.iter().enumerate() {
+        if let Ok(context) = std::fs::read_to_string(cc.source_path()) {
+            include_index.add(unit, &context);
+        }
+    }
+
+    headers
+        .into_iter()
+        .filter(|x| !known_files.contains(x))
+        .filter_map(|header| {
+            let unit = best_matching_unit(&header, &unit_directories, &include_index)?;
+            Some(header_entry(
+                &header,
+                &compile_commands[unit],
+                extra_wrappers,
+            ))
+        })
+        .collect()
+}
+
+/// Returns the absolute `-I` and `-iquote` directories of a compile command.
+fn include_directories(cc: &CompileCommand) -> Vec<String> {
+    let mut directories = Vec::new();
+    let mut arguments = cc.arguments.iter();
+    while let Some(arg) = arguments.next() {
+        let directory = SCANNED_INCLUDE_OPTIONS
+            .iter()
+            .find_map(|option| arg.strip_prefix(option));
+        let directory = match directory {
+            Some("") => arguments.next().map(|x| x.as_str()),
+            Some(directory) => Some(directory),
+            None => None,
+        };
+        if let Some(directory) = directory {
+            directories.push(CompileCommand::normalize_path(
+                &Path::new(&cc.directory).join(directory),
+            ));
+        }
+    }
+    directories
+}
+
+/// Collects the header files under a directory, recursively, skipping the directories
+/// already scanned.
+fn collect_headers(
+    directory: &Path,
+    depth: usize,
+    scanned: &mut HashSet<PathBuf>,
+    headers: &mut BTreeSet<String>,
+) {
+    if depth > MAX_SCAN_DEPTH || !scanned.insert(directory.to_path_buf()) {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if headers.len() >= MAX_HEADERS {
+            return;
+        }
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            collect_headers(&path, depth + 1, scanned, headers);
+        } else if path
+            .extension()
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| HEADER_EXTENSIONS.contains(&x))
+        {
+            headers.insert(CompileCommand::normalize_path(&path));
+        }
+    }
+}
+
+/// The `#include`s of the translation units, by the file name of the included path.
+#[derive(Default)]
+struct IncludeIndex {
+    /// The included path as written and the index of the including unit.
+    by_file_name: HashMap<String, Vec<(String, usize)>>,
+}
+
+impl IncludeIndex {
+    /// Adds the `#include`s of the source of a translation unit.
+    fn add(&mut self, unit: usize, context: &str) {
+        for line in context.lines() {
+            let Some(included) = line
+                .trim_start()
+                .strip_prefix('#')
+                .map(|x| x.trim_start())
+                .and_then(|x| x.strip_prefix("include"))
+            else {
+                continue;
+            };
+            let included = included
+                .trim()
+                .trim_matches(|c| c == '"' || c == '<' || c == '>');
+            if let Some(file_name) = Path::new(included).file_name().and_then(|x| x.to_str()) {
+                self.by_file_name
+                    .entry(file_name.to_string())
+                    .or_default()
+                    .push((included.to_string(), unit));
+            }
+        }
+    }
+
+    /// Returns the units with an `#include` naming the header.
+    fn includers(&self, header: &str) -> HashSet<usize> {
+        Path::new(header)
+            .file_name()
+            .and_then(|x| x.to_str())
+            .and_then(|x| self.by_file_name.get(x))
+            .into_iter()
+            .flatten()
+            .filter(|(included, _)| Path::new(header).ends_with(included))
+            .map(|(_, unit)| *unit)
+            .collect()
+    }
+}
+
+/// Returns the index of the translation unit whose flags suit a header best.
+///
+/// # Examples
+///
+/// ```ignore
+/// let unit = best_matching_unit("/work/src/a.h", &unit_directories, &include_index);
+/// ```
+fn best_matching_unit(
+    header: &str,
+    unit_directories: &[Option<PathBuf>],
+    include_index: &IncludeIndex,
+) -> Option<usize> {
+    let header_directory = Path::new(header).parent()?;
+    let ranked = unit_directories
+        .iter()
+        .map(|directory| {
+            let same_directory = directory.as_deref() == Some(header_directory);
+            let shared = directory
+                .as_deref()
+                .map(|x| shared_component_count(x, header_directory))
+                .unwrap_or(0);
+            (same_directory, shared)
+        })
+        .collect::<Vec<_>>();
+    let best = ranked.iter().max().copied()?;
+    // units sharing no directory with the header are only used if they include it
+    let mut candidates = (0..ranked.len()).filter(|x| best.1 == 0 || ranked[*x] == best);
+    let includers = include_index.includers(header);
+    match candidates.clone().find(|x| includers.contains(x)) {
+        Some(unit) => Some(unit),
+        None if best.1 > 0 => candidates.next(),
+        None => None,
+    }
+}
+
+/// Returns the number of leading normal components two paths share.
+fn shared_component_count(a: &Path, b: &Path) -> usize {
+    a.components()
+        .zip(b.components())
+        .take_while(|(x, y)| x == y)
+        .filter(|(x, _)| matches!(x, Component::Normal(_)))
+        .count()
+}
+
+/// Creates the entry of a header from the flags of a translation unit.
+fn header_entry(header: &str, tu: &CompileCommand, extra_wrappers: &[String]) -> CompileCommand {
+    let is_c_unit = Path::new(&tu.file)
+        .extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| C_SOURCE_EXTENSIONS.contains(&x));
+    let is_c_header = is_c_unit && header.ends_with(".h");
+    let language = if is_c_header {
+        "c-header"
+    } else {
+        "c++-header"
+    };
+
+    let compiler_index = CompileCommand::compiler_index(&tu.arguments, extra_wrappers);
+    let mut arguments = tu
+        .arguments
+        .iter()
+        .take(compiler_index + 1)
+        .cloned()
+        .collect::<Vec<_>>();
+    arguments.extend(["-x".to_string(), language.to_string()]);
+    let mut flags = tu.compile_flags(extra_wrappers).into_iter();
+    while let Some(flag) = flags.next() {
+        // the language of the unit does not apply to the header
+        if flag == "-x" {
+            flags.next();
+        } else if !flag.starts_with("-x") {
+            arguments.push(flag);
+        }
+    }
+    arguments.push(header.to_string());
+
+    CompileCommand {
+        command: CompileCommand::join_the_arguments_as_commands(&arguments),
+        arguments,
+        directory: tu.directory.clone(),
+        file: header.to_string(),
+        output: "".to_string(),
+        source: tu.source.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_directories(directories: &[&str]) -> Vec<Option<PathBuf>> {
+        directories.iter().map(|x| Some(PathBuf::from(x))).collect()
+    }
+
+    #[test]
+    fn a_unit_in_the_same_directory_wins() {
+        let units = unit_directories(&["/work/lib", "/work/src", "/work/src/sub"]);
+        let index = IncludeIndex::default();
+        assert_eq!(best_matching_unit("/work/src/a.h", &units, &index), Some(1));
+    }
+
+    #[test]
+    fn the_longest_shared_prefix_wins_without_a_unit_in_the_same_directory() {
+        let units = unit_directories(&["/work/lib", "/work/src/sub"]);
+        let index = IncludeIndex::default();
+        assert_eq!(
+            best_matching_unit("/work/src/a/a.h", &units, &index),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn an_including_unit_breaks_ties() {
+        let units = unit_directories(&["/work/src", "/work/src"]);
+        let mut index = IncludeIndex::default();
+        index.add(0, "#include <vector>\n");
+        index.add(1, "#include \"src/a.h\"\n");
+        assert_eq!(best_matching_unit("/work/src/a.h", &units, &index), Some(1));
+    }
+
+    #[test]
+    fn an_unrelated_unit_is_only_used_if_it_includes_the_header() {
+        let units = unit_directories(&["/other/x", "/else/y"]);
+        let mut index = IncludeIndex::default();
+        assert_eq!(best_matching_unit("/work/inc/a.h", &units, &index), None);
+        index.add(1, "  #  include \"inc/a.h\"\n");
+        assert_eq!(best_matching_unit("/work/inc/a.h", &units, &index), Some(1));
+    }
+
+    #[test]
+    fn an_include_must_name_the_end_of_the_header_path() {
+        let mut index = IncludeIndex::default();
+        index.add(0, "#include \"other/a.h\"\n#include \"ba.h\"\n");
+        assert!(index.includers("/work/inc/a.h").is_empty());
+        assert_eq!(index.includers("/work/other/a.h"), HashSet::from([0]));
+        assert!(index.includers("/work/another/a.h").is_empty());
+    }
+
+    #[test]
+    fn quote_directories_are_scanned_but_not_system_ones() {
+        let cc = CompileCommand {
+            arguments: [
+                "g++", "-Iinc", "-iquote", "q", "-isystem", "/sys", "-c", "a.cc",
+            ]
+            .map(|x| x.to_string())
+            .to_vec(),
+            directory: "/work".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(include_directories(&cc), vec!["/work/inc", "/work/q"]);
+    }
+}
@@ -1,4 +1,17 @@
 pub mod postprocess_config;
 pub mod arg_parser;
 pub mod compile_commands;
+pub mod clangd_config;
+pub mod compile_flags;
+pub mod duplicate_report;
+pub mod flag_editor;
+pub mod flag_translation;
+pub mod hash_utility;
+pub mod header_entries;
+pub mod recheck_build;
+pub mod response_files;
+pub mod selection_history;
+pub mod split_database;
+pub mod unit_report;
+pub mod vscode_config;
 pub mod skim_utility;
@@ -1,57 +1,340 @@
 use rayon::prelude::*;
 
 use ccj_postprocess::arg_parser;
-use ccj_postprocess::compile_commands::CompileCommand;
+use ccj_postprocess::clangd_config;
+use ccj_postprocess::compile_commands::{
+    CompileCommand, DedupKey, EntrySource, OutputFormat, SortKey,
+};
+use ccj_postprocess::compile_flags;
+use ccj_postprocess::duplicate_report::DuplicateReport;
+use ccj_postprocess::flag_editor;
+use ccj_postprocess::flag_translation::FlagTranslator;
+use ccj_postprocess::header_entries;
 use ccj_postprocess::postprocess_config::PostProcessConfig;
+use ccj_postprocess::recheck_build;
+use ccj_postprocess::response_files;
+use ccj_postprocess::selection_history::SelectionHistory;
 use ccj_postprocess::skim_utility;
+use ccj_postprocess::split_database;
+use ccj_postprocess::unit_report::UnitReport;
+use ccj_postprocess::vscode_config::CppProperties;
 use std::path::Path;
 fn main() {
     let arg_parser = arg_parser::ArgParser::parse();
+    let postprocess_config = arg_parser.get_postprocess_config().map(|file| {
+        PostProcessConfig::parse_the_config(file)
+            .select_profile(arg_parser.get_profile().map(|x| x.as_str()))
+    });
+
+    // compile the compiler substitution once instead of for every entry
+    let flag_translator = postprocess_config.as_ref().map(FlagTranslator::from_config);
+    let wrappers = postprocess_config
+        .as_ref()
+        .map(|x| x.compiler_wrappers.clone())
+        .unwrap_or_default();
+    let path_rules = postprocess_config
+        .as_ref()
+        .map(|x| x.compile_path_rules())
+        .unwrap_or_default();
+
+    if let (Some(path), Some(ppc)) = (arg_parser.get_export_clangd(), &postprocess_config) {
+        let base_directory = std::path::absolute(path)
+            .ok()
+            .and_then(|x| x.parent().map(CompileCommand::normalize_path))
+            .unwrap_or_default();
+        let (yaml, warnings) = clangd_config::to_clangd_yaml(ppc, &base_directory);
+        for warning in warnings {
+            eprintln!("[Warning] {}", warning);
+        }
+        std::fs::write(path, yaml).unwrap_or_else(|_| panic!("cannot write the file {}", path));
+        return;
+    }
+
     let input_file = arg_parser.get_input_file().unwrap();
-    let postprocess_config = arg_parser
-        .get_postprocess_config()
-        .map(|file| PostProcessConfig::parse_the_config(file));
     let mut compile_commands = CompileCommand::parse(input_file);
-    
-    if arg_parser.skip_nonexisted_file() {
-        compile_commands.retain(|c| Path::new(&format!("{}/{}", c.directory, c.file)).exists());
-    }
+
+    // remember every entry of the explained file of every input before anything is dropped
+    let mut explained = arg_parser
+        .get_explain_file()
+        .map(|file| Explained::collect(&compile_commands, file));
 
     if let Some(append_path) = arg_parser.get_append_files() {
         for a_path in append_path.split(',') {
             let mut append_compile_commands = CompileCommand::parse(a_path);
-            compile_commands.append(&mut append_compile_commands);
+            if let (Some(explained), Some(file)) = (&mut explained, arg_parser.get_explain_file()) {
+                explained.extend(Explained::collect(&append_compile_commands, file));
+            }
+            match arg_parser.get_append_mode().unwrap().as_str() {
+                "concat" => compile_commands.append(&mut append_compile_commands),
+                "overlay" => {
+                    CompileCommand::overlay(&mut compile_commands, append_compile_commands);
+                    Explained::note_missing(&mut explained, &compile_commands, |entry| {
+                        let replacements = compile_commands
+                            .iter()
+                            .filter(|cc| cc.source.path == a_path && cc.source_path() == entry.path)
+                            .map(|cc| cc.source.to_string())
+                            .collect::<Vec<_>>();
+                        format!("replaced {} by {}", entry.source, replacements.join(", "))
+                    });
+                }
+                _ => {
+                    unreachable!();
+                }
+            }
+        }
+    }
+
+    if arg_parser.is_expand_rsp() {
+        response_files::expand_response_files(&mut compile_commands);
+    }
+
+    if let Some(format) = arg_parser.get_report_duplicates() {
+        // the flags as written in the inputs, before the postprocess rules rewrite them
+        let reports = DuplicateReport::collect(&compile_commands);
+        match format.as_str() {
+            "text" => DuplicateReport::dump_text(&reports),
+            "json" => DuplicateReport::dump_json(&reports),
+            _ => {
+                unreachable!();
+            }
         }
+        return;
+    }
+
+    if let Some(remove_list) = arg_parser.get_remove_entries() {
+        let context = std::fs::read_to_string(remove_list)
+            .unwrap_or_else(|_| panic!("cannot open the remove list {}", remove_list));
+        let files = context
+            .lines()
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty() && !x.starts_with('#'))
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+        CompileCommand::remove_entries(&mut compile_commands, &files);
     }
 
-    match arg_parser.get_keep_duplicated().unwrap().as_str() {
+    if arg_parser.skip_nonexisted_file() {
+        compile_commands.retain(|c| Path::new(&c.source_path()).exists());
+    }
+
+    let dedup_key = match arg_parser.get_dedup_key().unwrap().as_str() {
+        "file" => DedupKey::File,
+        "file_output" => DedupKey::FileAndOutput,
+        _ => {
+            unreachable!();
+        }
+    };
+    let keep_duplicated = arg_parser.get_keep_duplicated().unwrap();
+    if postprocess_config
+        .as_ref()
+        .is_some_and(|x| !x.prefer.is_empty()) &&
+        keep_duplicated != "keep" &&
+        dedup_key == DedupKey::File
+    {
+        eprintln!(
+            "[Warning] the prefer rules have no effect: --keep-duplicated {} leaves one entry per file, use --keep-duplicated keep or --dedup-key file_output",
+            keep_duplicated
+        );
+    }
+    match keep_duplicated.as_str() {
         "keep" => {
             // do nothing
         }
         "retain_first" => {
-            compile_commands = CompileCommand::deduplicate_with_retain_first(compile_commands);
+            compile_commands =
+                CompileCommand::deduplicate_with_retain_first(compile_commands, dedup_key);
         }
         "retain_last" => {
             compile_commands.reverse();
-            compile_commands = CompileCommand::deduplicate_with_retain_first(compile_commands);
+            compile_commands =
+                CompileCommand::deduplicate_with_retain_first(compile_commands, dedup_key);
             compile_commands.reverse();
         }
+        "merge" => {
+            compile_commands =
+                CompileCommand::deduplicate_with_merge(compile_commands, dedup_key, &wrappers);
+            Explained::note_missing(&mut explained, &compile_commands, |entry| {
+                let kept = compile_commands
+                    .iter()
+                    .find(|cc| cc.source_path() == entry.path)
+                    .map(|cc| cc.source.to_string())
+                    .unwrap_or_default();
+                format!("merged  {} into {}", entry.source, kept)
+            });
+        }
         _ => {
             unreachable!();
         }
     }
 
     if let Some(ppc) = &postprocess_config {
-        CompileCommand::process_config(&mut compile_commands, &ppc);
+        CompileCommand::process_config(&mut compile_commands, ppc);
+        // before postprocessing, so the rules see the flags that `remove` strips
+        compile_commands = CompileCommand::select_preferred(compile_commands, &ppc.prefer);
     }
 
     compile_commands
         .par_iter_mut()
-        .for_each(|x| x.postprocess(&postprocess_config));
+        .for_each(|x| x.postprocess(&postprocess_config, flag_translator.as_ref(), &path_rules));
+
+    if arg_parser.is_synthesize_headers() {
+        let headers = header_entries::synthesize_header_entries(&compile_commands, &wrappers);
+        compile_commands.extend(headers);
+    }
+
+    if let Some(sort) = arg_parser.get_sort() {
+        let sort_key = match sort.as_str() {
+            "file" => SortKey::File,
+            "directory" => SortKey::Directory,
+            "output" => SortKey::Output,
+            _ => {
+                unreachable!();
+            }
+        };
+        CompileCommand::sort(&mut compile_commands, sort_key);
+    }
+    let format = match arg_parser.get_format().unwrap().as_str() {
+        "pretty" => OutputFormat::Pretty,
+        "compact" => OutputFormat::Compact,
+        _ => {
+            unreachable!();
+        }
+    };
+
+    if arg_parser.is_edit_flags() {
+        let config_file = arg_parser.get_postprocess_config().unwrap();
+        flag_editor::edit_flags(
+            &compile_commands,
+            config_file,
+            arg_parser.get_profile().map(|x| x.as_str()),
+            &wrappers,
+        );
+        return;
+    }
 
-    // Handle interactive file selection
-    if arg_parser.is_select_file() {
-        skim_utility::select_cpp_files(&compile_commands);
+    // Handle interactive file selection, or its matching alone with a query
+    if arg_parser.is_select_file() || arg_parser.get_select_query().is_some() {
+        // the command line takes precedence over the postprocess config
+        let split = |x: &String| x.split(',').map(|x| x.to_string()).collect::<Vec<_>>();
+        let extensions = arg_parser
+            .get_select_extensions()
+            .map(split)
+            .or_else(|| {
+                postprocess_config
+                    .as_ref()
+                    .map(|x| x.select_extensions.clone())
+                    .filter(|x| !x.is_empty())
+            })
+            .unwrap_or_else(|| {
+                skim_utility::DEFAULT_SELECT_EXTENSIONS
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect()
+            });
+        let languages = arg_parser
+            .get_select_languages()
+            .map(split)
+            .or_else(|| {
+                postprocess_config
+                    .as_ref()
+                    .map(|x| x.select_languages.clone())
+            })
+            .unwrap_or_default();
+        let history_file = if arg_parser.is_no_history() {
+            None
+        } else {
+            SelectionHistory::default_path()
+        };
+        let selected = match arg_parser.get_select_query() {
+            Some(query) => skim_utility::filter_cpp_files(
+                &compile_commands,
+                &extensions,
+                &languages,
+                query,
+                arg_parser.get_select_limit().copied(),
+                history_file.as_deref(),
+                &wrappers,
+            ),
+            None => skim_utility::select_cpp_files(
+                &compile_commands,
+                &extensions,
+                &languages,
+                history_file.as_deref(),
+                &wrappers,
+            ),
+        };
+        if let Some(query) = arg_parser.get_select_query() &&
+            selected.is_empty()
+        {
+            eprintln!("No file matches the query {}.", query);
+            std::process::exit(1);
+        }
+        match arg_parser.get_select_output().unwrap().as_str() {
+            "paths" => {
+                for cc in &selected {
+                    cc.dump_full_path();
+                }
+            }
+            "commands" => {
+                for cc in &selected {
+                    println!("{}, {}", cc.directory, cc.command);
+                }
+            }
+            "ccj" => CompileCommand::dump_ccj(&selected, arg_parser.is_emit_source(), format),
+            _ => {
+                unreachable!();
+            }
+        }
+        return;
+    }
+
+    if let Some(format) = arg_parser.get_report_units() {
+        let reports = UnitReport::collect(&compile_commands, &wrappers);
+        let delimiter = match format.as_str() {
+            "csv" => ',',
+            "tsv" => '\t',
+            _ => {
+                unreachable!();
+            }
+        };
+        print!("{}", UnitReport::to_table(&reports, delimiter));
+        return;
+    }
+
+    if let Some(path) = arg_parser.get_export_vscode() {
+        CppProperties::collect(&compile_commands, &wrappers).write(path);
+        return;
+    }
+
+    if arg_parser.is_emit_compile_flags() {
+        let roots = arg_parser
+            .get_compile_flags_roots()
+            .map(|x| x.split(',').map(|x| x.to_string()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let shared_flags =
+            compile_flags::shared_flags_by_directory(&compile_commands, &roots, &wrappers);
+        compile_flags::write_compile_flags(&shared_flags);
+        return;
+    }
+
+    let check_template = arg_parser.get_check_template().unwrap();
+    if let Some(path) = arg_parser.get_emit_ninja() {
+        let build_directory = std::path::absolute(path)
+            .ok()
+            .and_then(|x| x.parent().map(CompileCommand::normalize_path))
+            .unwrap_or_default();
+        let ninja = recheck_build::to_ninja(
+            &compile_commands,
+            check_template,
+            &build_directory,
+            &wrappers,
+        );
+        std::fs::write(path, ninja).unwrap_or_else(|_| panic!("cannot write the file {}", path));
+        return;
+    }
+    if let Some(path) = arg_parser.get_emit_script() {
+        let script = recheck_build::to_script(&compile_commands, check_template, &wrappers);
+        std::fs::write(path, script).unwrap_or_else(|_| panic!("cannot write the file {}", path));
         return;
     }
 
@@ -62,14 +345,94 @@ fn main() {
         return;
     }
 
+    if let Some(explained) = explained {
+        for entry in explained {
+            match (
+                entry.outcome,
+                compile_commands.iter().find(|cc| cc.source == entry.source),
+            ) {
+                (Some(outcome), _) => println!("{}", outcome),
+                (None, Some(cc)) => {
+                    println!("kept    {}: {}, {}", entry.source, cc.directory, cc.command)
+                }
+                (None, None) => println!("dropped {}", entry.source),
+            }
+        }
+        return;
+    }
+
     if let Some(file) = arg_parser.find_the_command() {
         for cc in compile_commands {
-            if cc.file == *file || format!("{}/{}", cc.directory, cc.file) == *file {
+            if cc.is_the_file(file) {
                 println!("{}, {}", cc.directory, cc.command);
             }
         }
         return;
     }
 
-    CompileCommand::dump_ccj(&compile_commands);
+    if let Some(rsp_directory) = arg_parser.get_factor_rsp() {
+        let min_users = *arg_parser.get_rsp_min_users().unwrap();
+        let files = response_files::factor_response_files(
+            &mut compile_commands,
+            rsp_directory,
+            min_users,
+            &wrappers,
+        );
+        response_files::write_response_files(&files);
+    }
+
+    if let Some(rules) = arg_parser.get_split_by() {
+        let rules = rules.split(',').map(|x| x.to_string()).collect::<Vec<_>>();
+        let (roots, rest) = split_database::split_by_prefix(compile_commands, &rules);
+        for (root, root_compile_commands) in roots {
+            let path = Path::new(&root).join("compile_commands.json");
+            CompileCommand::write_ccj(
+                &path.to_string_lossy(),
+                &root_compile_commands,
+                arg_parser.is_emit_source(),
+                format,
+            );
+            eprintln!("{}", path.display());
+        }
+        compile_commands = rest;
+    }
+
+    CompileCommand::dump_ccj(&compile_commands, arg_parser.is_emit_source(), format);
+}
+
+/// An entry of the `--explain` file and what happened to it.
+struct Explained {
+    source: EntrySource,
+    path: String,
+    outcome: Option<String>,
+}
+
+impl Explained {
+    /// Returns the entries of the file in the compile commands.
+    fn collect(compile_commands: &[CompileCommand], file: &str) -> Vec<Explained> {
+        compile_commands
+            .iter()
+            .filter(|cc| cc.is_the_file(file))
+            .map(|cc| Explained {
+                source: cc.source.clone(),
+                path: cc.source_path(),
+                outcome: None,
+            })
+            .collect()
+    }
+
+    /// Gives the entries that just left the compile commands their outcome.
+    fn note_missing(
+        explained: &mut Option<Vec<Explained>>,
+        compile_commands: &[CompileCommand],
+        outcome: impl Fn(&Explained) -> String,
+    ) {
+        for entry in explained.iter_mut().flatten() {
+            if entry.outcome.is_none() &&
+                !compile_commands.iter().any(|cc| cc.source == entry.source)
+            {
+                entry.outcome = Some(outcome(entry));
+            }
+        }
+    }
 }
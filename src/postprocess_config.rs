@@ -1,6 +1,8 @@
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::Path;
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct PostProcessConfig {
     #[serde(default)]
     pub remove: Vec<String>,
@@ -10,15 +12,296 @@ pub struct PostProcessConfig {
     pub replace: Vec<String>,
     #[serde(default)]
     pub ignore_files: Vec<String>,
+    #[serde(default)]
+    pub only_files: Vec<String>,
+    #[serde(default)]
+    pub ignore_directories: Vec<String>,
+    #[serde(default)]
+    pub only_directories: Vec<String>,
+    #[serde(default)]
+    pub ignore_compilers: Vec<String>,
+    #[serde(default)]
+    pub only_compilers: Vec<String>,
+    #[serde(default)]
+    pub ignore_outputs: Vec<String>,
+    #[serde(default)]
+    pub only_outputs: Vec<String>,
+    #[serde(default)]
+    pub ignore_flags: Vec<String>,
+    #[serde(default)]
+    pub only_flags: Vec<String>,
+    #[serde(default)]
+    pub ignore_inputs: Vec<String>,
+    #[serde(default)]
+    pub only_inputs: Vec<String>,
+    /// `remove` and `insert` rules that only apply to some files.
+    #[serde(default)]
+    pub path_rules: Vec<PathRule>,
+    /// Compiler launchers to skip in addition to ccache, distcc, icecc and sccache.
+    #[serde(default)]
+    pub compiler_wrappers: Vec<String>,
+    /// Removes the compiler wrappers instead of only skipping them; unset keeps them, and a
+    /// profile setting it overrides the base config.
+    #[serde(default)]
+    pub strip_compiler_wrappers: Option<bool>,
+    /// `"regex,compiler"` pairs; the first regex matching the compiler replaces it.
+    #[serde(default)]
+    pub compiler_map: Vec<String>,
+    /// Regexes of options dropped after a compiler substitution, on top of the built-in table.
+    #[serde(default)]
+    pub drop_flags: Vec<String>,
+    /// `"regex,replacement"` pairs applied after a compiler substitution, on top of the
+    /// built-in table.
+    #[serde(default)]
+    pub translate_flags: Vec<String>,
+    /// Rules picking one variant per file when several compile commands are kept for it.
+    #[serde(default)]
+    pub prefer: Vec<PreferRule>,
+    /// Extensions of the files offered by `--select_file`, without the dot.
+    #[serde(default)]
+    pub select_extensions: Vec<String>,
+    /// Languages of the files offered by `--select_file`, detected from `-x`, the compiler
+    /// driver and the extension; used instead of the extensions when set.
+    #[serde(default)]
+    pub select_languages: Vec<String>,
+    /// Named profiles layered on top of the rules above, selected with `--profile`.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, PostProcessConfig>,
+    /// The profile applied when `--profile` is not given.
+    #[serde(default)]
+    pub default_profile: Option<String>,
+}
+
+/// `remove` and `insert` rules that only apply to the files whose path matches a regex.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PathRule {
+    /// The regex matched against the normalized source path.
+    pub path_match: String,
+    #[serde(default)]
+    pub remove: Vec<String>,
+    #[serde(default)]
+    pub insert: Vec<String>,
+}
+
+/// A path rule with its path regex compiled once for all the entries.
+#[derive(Debug)]
+pub struct CompiledPathRule {
+    pub path_match: Regex,
+    pub rule: PathRule,
+}
+
+/// A rule picking one variant of a file that has several compile commands.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum PreferRule {
+    /// Prefers the variant with an argument matching the regex.
+    Flag(String),
+    /// Prefers the variant read from a compile database whose path matches the regex.
+    Input(String),
 }
 
 impl PostProcessConfig {
     pub fn parse_the_config(file: &str) -> PostProcessConfig {
         let pp = Path::new(file);
-        let context =
-            std::fs::read_to_string(pp).expect(&format!("cannot open the append file: {}", file));
+        let context = std::fs::read_to_string(pp)
+            .unwrap_or_else(|_| panic!("cannot open the append file: {}", file));
         let pp_config: PostProcessConfig = serde_json::from_str(&context)
             .expect("[Error] json fileparser fail for postprocess config");
         pp_config
     }
+
+    /// Resolves the config for a profile by layering it on top of the base rules.
+    ///
+    /// Falls back to `default_profile` when `profile` is `None`. Without either, the base
+    /// rules are returned unchanged. A profile cannot define `profiles` or `default_profile`
+    /// itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The parsed config holding the base rules and the profiles.
+    /// * `profile` - The profile name given on the command line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::postprocess_config::PostProcessConfig;
+    ///
+    /// let config: PostProcessConfig = serde_json::from_str(
+    ///     r#"{
+    ///         "remove": ["^-g$"],
+    ///         "default_profile": "clangd",
+    ///         "profiles": {
+    ///             "clangd": { "insert": ["-D__clang_analyzer__"] },
+    ///             "clang-tidy": { "remove": ["^-Werror$"] }
+    ///         }
+    ///     }"#,
+    /// )
+    /// .unwrap();
+    /// let clangd = config.select_profile(None);
+    /// assert_eq!(clangd.remove, vec!["^-g$"]);
+    /// assert_eq!(clangd.insert, vec!["-D__clang_analyzer__"]);
+    /// assert!(clangd.profiles.is_empty());
+    /// ```
+    pub fn select_profile(mut self, profile: Option<&str>) -> PostProcessConfig {
+        for (name, nested) in &self.profiles {
+            if !nested.profiles.is_empty() || nested.default_profile.is_some() {
+                panic!(
+                    "[Error] profile {} defines profiles or default_profile, which only the top level can",
+                    name
+                );
+            }
+        }
+        let name = match profile
+            .map(|x| x.to_string())
+            .or(self.default_profile.take())
+        {
+            Some(name) => name,
+            None => {
+                self.profiles.clear();
+                return self;
+            }
+        };
+        let mut profiles = std::mem::take(&mut self.profiles);
+        let selected = profiles.remove(&name).unwrap_or_else(|| {
+            let available = profiles.keys().cloned().collect::<Vec<_>>();
+            panic!(
+                "[Error] profile {} is not defined in the postprocess config, available: {}",
+                name,
+                available.join(", ")
+            )
+        });
+        self.extend(selected);
+        self
+    }
+
+    /// Compiles the path regexes of the path rules.
+    ///
+    /// # Returns
+    ///
+    /// - `Vec<CompiledPathRule>` - The path rules, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::postprocess_config::{PathRule, PostProcessConfig};
+    ///
+    /// let ppc = PostProcessConfig {
+    ///     path_rules: vec![PathRule {
+    ///         path_match: "^/work/kpar/".to_string(),
+    ///         ..Default::default()
+    ///     }],
+    ///     ..Default::default()
+    /// };
+    /// let path_rules = ppc.compile_path_rules();
+    /// assert!(path_rules[0].path_match.is_match("/work/kpar/a.cc"));
+    /// ```
+    pub fn compile_path_rules(&self) -> Vec<CompiledPathRule> {
+        self.path_rules
+            .iter()
+            .map(|rule| CompiledPathRule {
+                path_match: Regex::new(&rule.path_match).unwrap_or_else(|_| {
+                    panic!("[Error] invalid path_match regex {}", rule.path_match)
+                }),
+                rule: rule.clone(),
+            })
+            .collect()
+    }
+
+    /// Appends the rules of another config after the rules of this one.
+    ///
+    /// The offered files are a setting rather than rules, so a non-empty `select_extensions` or
+    /// `select_languages` of the other config replaces the one of this config.
+    fn extend(&mut self, other: PostProcessConfig) {
+        self.remove.extend(other.remove);
+        self.insert.extend(other.insert);
+        self.replace.extend(other.replace);
+        self.ignore_files.extend(other.ignore_files);
+        self.only_files.extend(other.only_files);
+        self.ignore_directories.extend(other.ignore_directories);
+        self.only_directories.extend(other.only_directories);
+        self.ignore_compilers.extend(other.ignore_compilers);
+        self.only_compilers.extend(other.only_compilers);
+        self.ignore_outputs.extend(other.ignore_outputs);
+        self.only_outputs.extend(other.only_outputs);
+        self.ignore_flags.extend(other.ignore_flags);
+        self.only_flags.extend(other.only_flags);
+        self.ignore_inputs.extend(other.ignore_inputs);
+        self.only_inputs.extend(other.only_inputs);
+        self.path_rules.extend(other.path_rules);
+        self.compiler_wrappers.extend(other.compiler_wrappers);
+        if other.strip_compiler_wrappers.is_some() {
+            self.strip_compiler_wrappers = other.strip_compiler_wrappers;
+        }
+        // the profile's compiler mapping takes precedence over the base one
+        let mut compiler_map = other.compiler_map;
+        compiler_map.append(&mut self.compiler_map);
+        self.compiler_map = compiler_map;
+        let mut prefer = other.prefer;
+        prefer.append(&mut self.prefer);
+        self.prefer = prefer;
+        self.drop_flags.extend(other.drop_flags);
+        self.translate_flags.extend(other.translate_flags);
+        if !other.select_extensions.is_empty() {
+            self.select_extensions = other.select_extensions;
+        }
+        if !other.select_languages.is_empty() {
+            self.select_languages = other.select_languages;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_profile_replaces_the_offered_files() {
+        let config: PostProcessConfig = serde_json::from_str(
+            r#"{
+                "select_extensions": ["cc", "c"],
+                "select_languages": ["c++"],
+                "profiles": {
+                    "cuda": { "select_languages": ["cuda"] }
+                }
+            }"#,
+        )
+        .unwrap();
+        let cuda = config.select_profile(Some("cuda"));
+        assert_eq!(cuda.select_extensions, vec!["cc", "c"]);
+        assert_eq!(cuda.select_languages, vec!["cuda"]);
+    }
+
+    #[test]
+    fn a_profile_overrides_strip_compiler_wrappers() {
+        let config = || -> PostProcessConfig {
+            serde_json::from_str(
+                r#"{
+                    "strip_compiler_wrappers": true,
+                    "profiles": {
+                        "keep": { "strip_compiler_wrappers": false },
+                        "other": { "remove": ["^-g$"] }
+                    }
+                }"#,
+            )
+            .unwrap()
+        };
+        let keep = config().select_profile(Some("keep"));
+        assert_eq!(keep.strip_compiler_wrappers, Some(false));
+        let other = config().select_profile(Some("other"));
+        assert_eq!(other.strip_compiler_wrappers, Some(true));
+    }
+
+    #[test]
+    #[should_panic(expected = "profile nested defines profiles or default_profile")]
+    fn a_profile_cannot_nest_profiles() {
+        let config: PostProcessConfig = serde_json::from_str(
+            r#"{
+                "profiles": {
+                    "nested": { "default_profile": "inner", "profiles": { "inner": {} } }
+                }
+            }"#,
+        )
+        .unwrap();
+        config.select_profile(None);
+    }
 }
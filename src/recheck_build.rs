@@ -0,0 +1,266 @@
+use crate::compile_commands::CompileCommand;
+use crate::hash_utility;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// The command template of a recheck when none is given.
+pub const DEFAULT_CHECK_TEMPLATE: &str = "{command} -fsyntax-only";
+
+/// The directory of the stamp files written by the Ninja recheck.
+const STAMP_DIRECTORY: &str = ".ccj_check";
+
+/// Expands the check template of a translation unit.
+///
+/// `{command}` is the compiler with the compile flags and the source file, without the
+/// output and dependency-file options; `{file}` is the source path and `{directory}` the
+/// working directory of the entry.
+///
+/// # Arguments
+///
+/// * `cc` - The postprocessed compile command.
+/// * `template` - The command template.
+/// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+///
+/// # Examples
+///
+/// ```
+/// use ccj_postprocess::compile_commands::CompileCommand;
+/// use ccj_postprocess::recheck_build;
+///
+/// let cc = CompileCommand {
+///     command: "".to_string(),
+///     arguments: ["g++", "-DNAME=\"a b\"", "-c", "a.cc", "-o", "a.o"].map(String::from).to_vec(),
+///     directory: "/work".to_string(),
+///     file: "a.cc".to_string(),
+///     output: "".to_string(),
+///     ..Default::default()
+/// };
+/// assert_eq!(
+///     recheck_build::check_command(&cc, recheck_build::DEFAULT_CHECK_TEMPLATE, &[]),
+///     "g++ '-DNAME=\"a b\"' /work/a.cc -fsyntax-only"
+/// );
+/// ```
+pub fn check_command(cc: &CompileCommand, template: &str, extra_wrappers: &[String]) -> String {
+    expand_template(cc, template, &[], extra_wrappers)
+}
+
+/// Expands the check template, with the dependency-file options added to `{command}`.
+fn expand_template(
+    cc: &CompileCommand,
+    template: &str,
+    dependency_flags: &[String],
+    extra_wrappers: &[String],
+) -> String {
+    let source_path = cc.source_path();
+    let compiler_index = CompileCommand::compiler_index(&cc.arguments, extra_wrappers);
+    let command = cc
+        .arguments
+        .iter()
+        .take(compiler_index + 1)
+        .cloned()
+        .chain(cc.compile_flags(extra_wrappers))
+        .chain(dependency_flags.iter().cloned())
+        .chain([source_path.clone()])
+        .map(|x| shell_quote(&x))
+        .collect::<Vec<_>>()
+        .join(" ");
+    template
+        .replace("{command}", &command)
+        .replace("{file}", &shell_quote(&source_path))
+        .replace("{directory}", &shell_quote(&cc.directory))
+}
+
+/// Creates a `build.ninja` that rechecks every translation unit.
+///
+/// Each translation unit becomes a build edge from its source file to a stamp file under
+/// `.ccj_check`, so Ninja runs the checks in parallel and only reruns those whose source
+/// changed or whose check failed. The stamp is named after the source file and a hash of its
+/// path and output, so the variants of a file get their own stamps. When the template uses
+/// `{command}`, it also writes a dependency file next to the stamp, so a change to an included
+/// header reruns the check; other templates are only rerun when the source file changes.
+///
+/// # Arguments
+///
+/// * `compile_commands` - The postprocessed compile commands.
+/// * `template` - The command template, see `check_command`.
+/// * `build_directory` - The absolute directory the `build.ninja` is written to.
+/// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+///
+/// # Examples
+///
+/// ```
+/// use ccj_postprocess::recheck_build;
+///
+/// let ninja = recheck_build::to_ninja(&[], recheck_build::DEFAULT_CHECK_TEMPLATE, "/work", &[]);
+/// assert!(ninja.starts_with("rule check\n"));
+/// ```
+pub fn to_ninja(
+    compile_commands: &[CompileCommand],
+    template: &str,
+    build_directory: &str,
+    extra_wrappers: &[String],
+) -> String {
+    // only {command} carries the dependency-file options, Ninja fails on a missing depfile
+    let writes_depfile = template.contains("{command}");
+    let mut ninja = String::from(
+        "rule check\n  command = mkdir -p $$(dirname $out) && (cd $dir && $check) && touch $out\n  description = CHECK $in\n",
+    );
+    if writes_depfile {
+        ninja.push_str("  depfile = $out.d\n  deps = gcc\n");
+    }
+    let mut stamps = HashSet::new();
+    for cc in compile_commands {
+        let source_path = cc.source_path();
+        let stamp = stamp_path(&source_path, &cc.output, &mut stamps);
+        // the check runs in the directory of the entry, so the dependency file path is absolute
+        let dependency_flags = [
+            "-MD".to_string(),
+            "-MF".to_string(),
+            format!("{}/{}.d", build_directory.trim_end_matches('/'), stamp),
+            "-MT".to_string(),
+            stamp.clone(),
+        ];
+        ninja.push_str(&format!(
+            "\nbuild {}: check {}\n  dir = {}\n  check = {}\n",
+            ninja_path(&stamp),
+            ninja_path(&source_path),
+            shell_quote(&cc.directory).replace('$', "$$"),
+            expand_template(cc, template, &dependency_flags, extra_wrappers).replace('$', "$$")
+        ));
+    }
+    ninja
+}
+
+/// Returns a stamp path no other entry uses, from the file name and a hash of the source path
+/// and the output.
+fn stamp_path(source_path: &str, output: &str, stamps: &mut HashSet<String>) -> String {
+    let file_name = Path::new(source_path)
+        .file_name()
+        .and_then(|x| x.to_str())
+        .unwrap_or_default();
+    let hash = hash_utility::fnv1a(&[source_path.to_string(), output.to_string()]);
+    let mut stamp = format!("{}/{}_{:016x}.stamp", STAMP_DIRECTORY, file_name, hash);
+    // entries kept with the same file and output
+    let mut count = 1;
+    while !stamps.insert(stamp.clone()) {
+        count += 1;
+        stamp = format!(
+            "{}/{}_{:016x}_{}.stamp",
+            STAMP_DIRECTORY, file_name, hash, count
+        );
+    }
+    stamp
+}
+
+/// Creates a shell script that rechecks every translation unit in turn.
+///
+/// The script runs every check and exits non-zero if any of them failed.
+///
+/// # Arguments
+///
+/// * `compile_commands` - The postprocessed compile commands.
+/// * `template` - The command template, see `check_command`.
+/// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+///
+/// # Examples
+///
+/// ```
+/// use ccj_postprocess::recheck_build;
+///
+/// let script = recheck_build::to_script(&[], recheck_build::DEFAULT_CHECK_TEMPLATE, &[]);
+/// assert_eq!(script, "#!/bin/sh\nstatus=0\nexit $status\n");
+/// ```
+pub fn to_script(
+    compile_commands: &[CompileCommand],
+    template: &str,
+    extra_wrappers: &[String],
+) -> String {
+    let mut script = String::from("#!/bin/sh\nstatus=0\n");
+    for cc in compile_commands {
+        script.push_str(&format!(
+            "(cd {} && {}) || status=1\n",
+            shell_quote(&cc.directory),
+            check_command(cc, template, extra_wrappers)
+        ));
+    }
+    script.push_str("exit $status\n");
+    script
+}
+
+/// Quotes an argument for a POSIX shell if it has any special character.
+fn shell_quote(arg: &str) -> String {
+    let is_plain = !arg.is_empty() &&
+        arg.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=+,:@%".contains(c));
+    if is_plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Escapes a path in a Ninja build line.
+fn ninja_path(path: &str) -> String {
+    path.replace('$', "$$")
+        .replace(' ', "$ ")
+        .replace(':', "$:")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamps_of_similar_paths_do_not_collide() {
+        let mut stamps = HashSet::new();
+        let a = stamp_path("/a/b_c.cc", "", &mut stamps);
+        let b = stamp_path("/a_b/c.cc", "", &mut stamps);
+        assert_ne!(a, b);
+        assert!(a.starts_with(".ccj_check/b_c.cc_"));
+    }
+
+    #[test]
+    fn variants_of_a_file_get_their_own_stamps() {
+        let mut stamps = HashSet::new();
+        let a = stamp_path("/work/a.cc", "a.o", &mut stamps);
+        let b = stamp_path("/work/a.cc", "a_debug.o", &mut stamps);
+        let c = stamp_path("/work/a.cc", "a.o", &mut stamps);
+        assert_ne!(a, b);
+        assert_eq!(c, a.replace(".stamp", "_2.stamp"));
+        assert_eq!(stamp_path("/work/a.cc", "a.o", &mut HashSet::new()), a);
+    }
+
+    #[test]
+    fn checks_write_a_dependency_file_next_to_the_stamp() {
+        let cc = CompileCommand {
+            command: "".to_string(),
+            arguments: ["g++", "-c", "a.cc"].map(String::from).to_vec(),
+            directory: "/work".to_string(),
+            file: "a.cc".to_string(),
+            output: "".to_string(),
+            ..Default::default()
+        };
+        let ninja = to_ninja(&[cc], DEFAULT_CHECK_TEMPLATE, "/build", &[]);
+        let stamp = stamp_path("/work/a.cc", "", &mut HashSet::new());
+        assert!(ninja.contains("  depfile = $out.d\n  deps = gcc\n"));
+        assert!(ninja.contains(&format!(
+            "  check = g++ -MD -MF /build/{}.d -MT {} /work/a.cc -fsyntax-only\n",
+            stamp, stamp
+        )));
+    }
+
+    #[test]
+    fn a_template_without_the_command_has_no_dependency_file() {
+        let cc = CompileCommand {
+            command: "".to_string(),
+            arguments: ["g++", "-c", "a.cc"].map(String::from).to_vec(),
+            directory: "/work".to_string(),
+            file: "a.cc".to_string(),
+            output: "".to_string(),
+            ..Default::default()
+        };
+        let ninja = to_ninja(&[cc], "clang-tidy {file}", "/build", &[]);
+        assert!(!ninja.contains("depfile"));
+        assert!(ninja.contains("  check = clang-tidy /work/a.cc\n"));
+    }
+}
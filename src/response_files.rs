@@ -0,0 +1,317 @@
+use crate::compile_commands::CompileCommand;
+use crate::hash_utility;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Options that may be moved into a response file, with whether their value is the next argument
+/// and the kind of block they belong to.
+const FACTORED_OPTIONS: [(&str, bool, BlockKind); 5] = [
+    ("-I", false, BlockKind::Include),
+    ("-isystem", true, BlockKind::Include),
+    ("-iquote", true, BlockKind::Include),
+    ("-D", false, BlockKind::Define),
+    ("-U", false, BlockKind::Define),
+];
+
+/// The kinds of blocks factored separately, as entries often share their includes but not their
+/// defines or the other way round.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BlockKind {
+    Include,
+    Define,
+}
+
+/// The fewest arguments worth moving into a response file.
+const MIN_BLOCK_LENGTH: usize = 2;
+
+/// How deep response files may reference other response files.
+const MAX_EXPANSION_DEPTH: usize = 16;
+
+/// Moves the include and define blocks shared by several entries into response files.
+///
+/// A block is a run of consecutive `-I`, `-isystem` and `-iquote` options after the compiler,
+/// or a run of consecutive `-D` and `-U` options, so shared includes are factored even when the
+/// defines differ. Every block used by at least `min_users` entries is replaced by
+/// `@<rsp_directory>/flags_<hash>.rsp`, so the compiler still sees the same arguments. The
+/// name only depends on the content of the block, so identical inputs give identical files.
+///
+/// # Arguments
+///
+/// * `compile_commands` - The postprocessed compile commands.
+/// * `rsp_directory` - The directory the response files are written to.
+/// * `min_users` - The fewest entries sharing a block before it is factored out.
+/// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+///
+/// # Returns
+///
+/// - `BTreeMap<String, Vec<String>>` - The arguments of each response file, by path.
+///
+/// # Examples
+///
+/// ```
+/// use ccj_postprocess::compile_commands::CompileCommand;
+/// use ccj_postprocess::response_files;
+///
+/// let entry = |file: &str| CompileCommand {
+///     command: "".to_string(),
+///     arguments: ["g++", "-O2", "-I/a", "-I/b", "-DX", "-c", file].map(String::from).to_vec(),
+///     directory: "/work".to_string(),
+///     file: file.to_string(),
+///     output: "".to_string(),
+///     ..Default::default()
+/// };
+/// let mut compile_commands = vec![entry("a.cc"), entry("b.cc")];
+/// let files = response_files::factor_response_files(&mut compile_commands, "/work/rsp", 2, &[]);
+/// let (path, arguments) = files.iter().next().unwrap();
+/// assert_eq!(arguments, &vec!["-I/a", "-I/b"]);
+/// assert_eq!(compile_commands[0].command, format!("g++ -O2 @{} -DX -c a.cc", path));
+/// ```
+pub fn factor_response_files(
+    compile_commands: &mut [CompileCommand],
+    rsp_directory: &str,
+    min_users: usize,
+    extra_wrappers: &[String],
+) -> BTreeMap<String, Vec<String>> {
+    let rsp_directory = std::path::absolute(rsp_directory)
+        .map(|x| CompileCommand::normalize_path(&x))
+        .unwrap_or_else(|_| panic!("[Error] invalid response file directory {}", rsp_directory));
+
+    let mut users = HashMap::<Vec<String>, usize>::new();
+    for cc in compile_commands.iter() {
+        for (start, end) in factored_blocks(&cc.arguments, extra_wrappers) {
+            *users.entry(cc.arguments[start..end].to_vec()).or_insert(0) += 1;
+        }
+    }
+
+    let mut files = BTreeMap::new();
+    for cc in compile_commands.iter_mut() {
+        // replace from the back so the earlier ranges stay valid
+        for (start, end) in factored_blocks(&cc.arguments, extra_wrappers)
+            .into_iter()
+            .rev()
+        {
+            let block = cc.arguments[start..end].to_vec();
+            if users[&block] < min_users {
+                continue;
+            }
+            let path = format!(
+                "{}/flags_{:016x}.rsp",
+                rsp_directory,
+                hash_utility::fnv1a(&block)
+            );
+            cc.arguments.splice(start..end, [format!("@{}", path)]);
+            files.insert(path, block);
+        }
+        cc.command = CompileCommand::join_the_arguments_as_commands(&cc.arguments);
+    }
+    files
+}
+
+/// Writes the response files created by `factor_response_files`.
+///
+/// # Arguments
+///
+/// * `files` - The arguments of each response file, by path.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ccj_postprocess::response_files;
+///
+/// let mut compile_commands = vec![];
+/// let files = response_files::factor_response_files(&mut compile_commands, "rsp", 2, &[]);
+/// response_files::write_response_files(&files);
+/// ```
+pub fn write_response_files(files: &BTreeMap<String, Vec<String>>) {
+    for (path, arguments) in files {
+        if let Some(parent) = Path::new(path).parent() {
+            std::fs::create_dir_all(parent)
+                .unwrap_or_else(|_| panic!("cannot create the directory {:?}", parent));
+        }
+        let context = arguments
+            .iter()
+            .map(|x| quote_argument(x) + "\n")
+            .collect::<String>();
+        std::fs::write(path, context).unwrap_or_else(|_| panic!("cannot write the file {}", path));
+    }
+}
+
+/// Replaces every `@file` argument that names an existing response file by its arguments.
+///
+/// Relative response files are resolved against the directory of the entry, and response
+/// files may reference other response files. Arguments naming no file are kept as they are.
+///
+/// # Arguments
+///
+/// * `compile_commands` - The compile commands.
+///
+/// # Examples
+///
+/// ```
+/// use ccj_postprocess::compile_commands::CompileCommand;
+/// use ccj_postprocess::response_files;
+///
+/// let directory = std::env::temp_dir().join("ccj_postprocess_expand_example");
+/// std::fs::create_dir_all(&directory).unwrap();
+/// std::fs::write(directory.join("flags.rsp"), "-I/a \"-DNAME=\\\"a b\\\"\"\n").unwrap();
+/// let mut compile_commands = vec![CompileCommand {
+///     command: "g++ @flags.rsp @missing.rsp -c a.cc".to_string(),
+///     arguments: vec![],
+///     directory: directory.to_string_lossy().into_owned(),
+///     file: "a.cc".to_string(),
+///     output: "".to_string(),
+///     ..Default::default()
+/// }];
+/// response_files::expand_response_files(&mut compile_commands);
+/// assert_eq!(
+///     compile_commands[0].arguments,
+///     vec!["g++", "-I/a", "-DNAME=\"a b\"", "@missing.rsp", "-c", "a.cc"]
+/// );
+/// ```
+pub fn expand_response_files(compile_commands: &mut [CompileCommand]) {
+    for cc in compile_commands {
+        let arguments = if cc.arguments.is_empty() {
+            cc.command.split(' ').map(|x| x.to_string()).collect()
+        } else {
+            cc.arguments.clone()
+        };
+        if !arguments.iter().any(|x| x.starts_with('@')) {
+            continue;
+        }
+        cc.arguments = expand_arguments(arguments, &cc.directory, 0);
+        cc.command = CompileCommand::join_the_arguments_as_commands(&cc.arguments);
+    }
+}
+
+/// Expands the response files of an argument list, recursively.
+fn expand_arguments(arguments: Vec<String>, directory: &str, depth: usize) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for arg in arguments {
+        let context = arg
+            .strip_prefix('@')
+            .filter(|_| depth < MAX_EXPANSION_DEPTH)
+            .and_then(|x| std::fs::read_to_string(Path::new(directory).join(x)).ok());
+        match context {
+            Some(context) => expanded.extend(expand_arguments(
+                split_arguments(&context),
+                directory,
+                depth + 1,
+            )),
+            None => expanded.push(arg),
+        }
+    }
+    expanded
+}
+
+/// Returns the ranges of the blocks of factored options after the compiler.
+fn factored_blocks(arguments: &[String], extra_wrappers: &[String]) -> Vec<(usize, usize)> {
+    let compiler_index = CompileCommand::compiler_index(arguments, extra_wrappers);
+    let mut blocks = Vec::new();
+    let mut start: Option<(usize, BlockKind)> = None;
+    let mut index = compiler_index + 1;
+    while index <= arguments.len() {
+        let option = arguments.get(index).and_then(|arg| {
+            FACTORED_OPTIONS
+                .iter()
+                .find(|(option, _, _)| arg.starts_with(option))
+                .map(|(option, has_value, kind)| {
+                    (if *has_value && arg == option { 2 } else { 1 }, *kind)
+                })
+                .filter(|(length, _)| index + length <= arguments.len())
+        });
+        // a block ends before an argument of another kind, or one not factored at all
+        if let Some((block_start, kind)) = start &&
+            option.is_none_or(|(_, x)| x != kind)
+        {
+            if index - block_start >= MIN_BLOCK_LENGTH {
+                blocks.push((block_start, index));
+            }
+            start = None;
+        }
+        match option {
+            Some((length, kind)) => {
+                start.get_or_insert((index, kind));
+                index += length;
+            }
+            None => index += 1,
+        }
+    }
+    blocks
+}
+
+/// Quotes an argument for a GCC-style response file if it needs it.
+fn quote_argument(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c)) {
+        return arg.to_string();
+    }
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Splits the content of a GCC-style response file into arguments.
+///
+/// Arguments are separated by whitespace; single and double quotes group characters, and a
+/// backslash takes the next character literally.
+fn split_arguments(context: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    let mut chars = context.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                let escaped = chars.next().unwrap_or('\\');
+                current.get_or_insert_with(String::new).push(escaped);
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, Some(_)) => current.get_or_insert_with(String::new).push(c),
+            ('\'' | '"', None) => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (c, None) if c.is_whitespace() => arguments.extend(current.take()),
+            (c, None) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    arguments.extend(current);
+    arguments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(arguments: &[&str]) -> Vec<String> {
+        arguments.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn include_and_define_runs_are_separate_blocks() {
+        let arguments = arguments(&[
+            "g++", "-I/a", "-isystem", "/b", "-DX", "-UY", "-O2", "-I/c", "-DZ", "-c", "a.cc",
+        ]);
+        assert_eq!(factored_blocks(&arguments, &[]), vec![(1, 4), (4, 6)]);
+    }
+
+    #[test]
+    fn shared_includes_are_factored_when_the_defines_differ() {
+        let entry = |file: &str, define: &str| CompileCommand {
+            command: "".to_string(),
+            arguments: arguments(&["g++", "-I/a", "-I/b", define, "-DCOMMON", "-c", file]),
+            directory: "/work".to_string(),
+            file: file.to_string(),
+            output: "".to_string(),
+            ..Default::default()
+        };
+        let mut compile_commands = vec![entry("a.cc", "-DA"), entry("b.cc", "-DB")];
+        let files = factor_response_files(&mut compile_commands, "/rsp", 2, &[]);
+        assert_eq!(
+            files.into_values().collect::<Vec<_>>(),
+            vec![arguments(&["-I/a", "-I/b"])]
+        );
+        assert!(compile_commands[0].arguments[1].starts_with("@/rsp/flags_"));
+        assert_eq!(
+            compile_commands[0].arguments[2..4],
+            arguments(&["-DA", "-DCOMMON"])
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use crate::compile_commands::CompileCommand;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The most files remembered; the ones with the lowest frecency are forgotten first.
+const MAX_ENTRIES: usize = 1000;
+
+/// How often and when a file was last selected.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct HistoryEntry {
+    /// How many times the file was selected.
+    pub count: u64,
+    /// When the file was last selected, in seconds since the Unix epoch.
+    pub last_used: u64,
+}
+
+/// The files selected in the picker, by full path.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(transparent)]
+pub struct SelectionHistory {
+    pub entries: BTreeMap<String, HistoryEntry>,
+}
+
+impl SelectionHistory {
+    /// Returns the path of the history file.
+    ///
+    /// The file is `ccj_postprocess/history.json` under `$XDG_STATE_HOME`, or under
+    /// `~/.local/state` when it is unset or not absolute.
+    ///
+    /// # Returns
+    ///
+    /// - `Option<PathBuf>` - The path of the history file, or `None` if no home directory is known.
+    pub fn default_path() -> Option<PathBuf> {
+        let state_home = std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|x| x.is_absolute())
+            .or_else(|| {
+                std::env::var_os("HOME").map(|x| PathBuf::from(x).join(".local").join("state"))
+            })?;
+        Some(state_home.join("ccj_postprocess").join("history.json"))
+    }
+
+    /// Reads the history file; a missing or unreadable file gives an empty history.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the history file.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::selection_history::SelectionHistory;
+    ///
+    /// let path = SelectionHistory::default_path().unwrap();
+    /// let history = SelectionHistory::load(&path);
+    /// ```
+    pub fn load(path: &Path) -> Self {
+        let Ok(context) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&context).unwrap_or_else(|_| {
+            eprintln!(
+                "[Warning] the selection history {} cannot be parsed, starting a new one",
+                path.display()
+            );
+            Self::default()
+        })
+    }
+
+    /// Writes the history file, creating its directory.
+    ///
+    /// The history is only a convenience, so a failure is reported as a warning.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the history file.
+    pub fn save(&self, path: &Path) {
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, serde_json::to_string_pretty(self).unwrap()));
+        if written.is_err() {
+            eprintln!(
+                "[Warning] cannot write the selection history {}",
+                path.display()
+            );
+        }
+    }
+
+    /// Records a selection of the files, forgetting the least relevant files beyond the limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The full paths of the selected files.
+    /// * `now` - The current time, in seconds since the Unix epoch.
+    pub fn record(&mut self, paths: &[String], now: u64) {
+        for path in paths {
+            let entry = self.entries.entry(path.clone()).or_default();
+            entry.count += 1;
+            entry.last_used = now;
+        }
+        if self.entries.len() > MAX_ENTRIES {
+            let mut scores = self
+                .entries
+                .keys()
+                .map(|x| (self.frecency(x, now), x.clone()))
+                .collect::<Vec<_>>();
+            scores.sort();
+            for (_, path) in scores.into_iter().take(self.entries.len() - MAX_ENTRIES) {
+                self.entries.remove(&path);
+            }
+        }
+    }
+
+    /// Returns how frequently and recently the file was selected.
+    ///
+    /// The selection count is weighted by the age of the last selection: 16 within an hour,
+    /// 8 within a day, 2 within a week and 1 after that. Unknown files score 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The full path of the file.
+    /// * `now` - The current time, in seconds since the Unix epoch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::selection_history::SelectionHistory;
+    ///
+    /// let mut history = SelectionHistory::default();
+    /// history.record(&["/work/a.cc".to_string()], 0);
+    /// history.record(&["/work/a.cc".to_string()], 0);
+    /// assert_eq!(history.frecency("/work/a.cc", 60), 32);
+    /// assert_eq!(history.frecency("/work/a.cc", 30 * 24 * 3600), 2);
+    /// assert_eq!(history.frecency("/work/b.cc", 60), 0);
+    /// ```
+    pub fn frecency(&self, path: &str, now: u64) -> u64 {
+        let Some(entry) = self.entries.get(path) else {
+            return 0;
+        };
+        let age = now.saturating_sub(entry.last_used);
+        let weight = match age {
+            0..3600 => 16,
+            3600..86400 => 8,
+            86400..604800 => 2,
+            _ => 1,
+        };
+        entry.count * weight
+    }
+
+    /// Sorts the compile commands by descending frecency, keeping the order of equal ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `compile_commands` - The compile commands offered in the picker.
+    /// * `now` - The current time, in seconds since the Unix epoch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    /// use ccj_postprocess::selection_history::SelectionHistory;
+    ///
+    /// let entry = |file: &str| CompileCommand {
+    ///     command: format!("g++ -c {}", file),
+    ///     arguments: vec![],
+    ///     directory: "/work".to_string(),
+    ///     file: file.to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let (a, b, c) = (entry("a.cc"), entry("b.cc"), entry("c.cc"));
+    /// let mut history = SelectionHistory::default();
+    /// history.record(&["/work/c.cc".to_string()], 100);
+    /// let mut offered = vec![&a, &b, &c];
+    /// history.rank(&mut offered, 100);
+    /// assert_eq!(offered.iter().map(|x| x.file.as_str()).collect::<Vec<_>>(), ["c.cc", "a.cc", "b.cc"]);
+    /// ```
+    pub fn rank(&self, compile_commands: &mut [&CompileCommand], now: u64) {
+        compile_commands
+            .sort_by_cached_key(|cc| std::cmp::Reverse(self.frecency(&cc.source_path(), now)));
+    }
+}
+
+/// Returns the current time, in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
+}
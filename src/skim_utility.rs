@@ -1,35 +1,73 @@
 use skim::prelude::*;
-use std::io::Cursor;
 use crate::compile_commands::CompileCommand;
+use crate::selection_history::{self, SelectionHistory};
+use std::path::Path;
+
+/// The extensions of the files offered when none are configured.
+pub const DEFAULT_SELECT_EXTENSIONS: [&str; 5] = ["cpp", "cxx", "cc", "c++", "C"];
+
+/// The key showing or hiding the compile command preview.
+const TOGGLE_PREVIEW_KEY: &str = "alt-p";
+
+/// Options naming an include directory, glued to their value or not.
+const INCLUDE_OPTIONS: [&str; 3] = ["-I", "-isystem", "-iquote"];
+
+/// Options defining or undefining a macro, glued to their value or not.
+const DEFINE_OPTIONS: [&str; 2] = ["-D", "-U"];
+
+/// A translation unit in the file picker, previewing its compile command.
+struct CompileCommandItem {
+    compile_command: CompileCommand,
+    path: String,
+    preview: String,
+}
+
+impl SkimItem for CompileCommandItem {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.path)
+    }
+
+    fn preview(&self, _context: PreviewContext) -> ItemPreview {
+        ItemPreview::Text(self.preview.clone())
+    }
+}
 
 /// Launches an interactive file selector for C++ files from compile commands.
-/// Filters for common C++ file extensions and presents them using skim.
-/// Outputs the full paths of selected files.
-pub fn select_cpp_files(compile_commands: &[CompileCommand]) {
-    // Filter for C++ files based on common extensions
-    let cpp_files: Vec<String> = compile_commands
-        .iter()
-        .filter(|cc| is_cpp_file(&cc.file))
-        .map(|cc| format!("{}/{}", cc.directory, cc.file))
-        .collect();
+/// Filters for the given extensions, or for the detected languages when any are given,
+/// and presents them using skim.
+/// The preview window shows the compile command of the highlighted file.
+/// With a history file, the most frequently and recently selected files are listed first
+/// and the selection is recorded in it.
+/// Returns the compile commands of the selected files, empty if the selection is cancelled.
+pub fn select_cpp_files(
+    compile_commands: &[CompileCommand],
+    extensions: &[String],
+    languages: &[String],
+    history_file: Option<&Path>,
+    extra_wrappers: &[String],
+) -> Vec<CompileCommand> {
+    // Filter for C++ files based on the extensions or the detected languages
+    let now = selection_history::now();
+    let mut history = history_file.map(SelectionHistory::load);
+    let cpp_files = offered_files(
+        compile_commands,
+        extensions,
+        languages,
+        history.as_ref(),
+        now,
+        extra_wrappers,
+    );
 
     if cpp_files.is_empty() {
         eprintln!("No C++ files found in compile commands.");
-        return;
+        return Vec::new();
     }
 
     // Create input for skim
-    let input = cpp_files.join("\n");
-    let item_reader = SkimItemReader::default();
-    let items = item_reader.of_bufread(Cursor::new(input));
+    let items = compile_command_items(cpp_files, extra_wrappers);
 
     // Configure skim options
-    let options = SkimOptionsBuilder::default()
-        .height("50%".to_string())
-        .multi(true)
-        .prompt("Select C++ files: ".to_string())
-        .build()
-        .unwrap();
+    let options = file_picker_options("Select C++ files: ", true);
 
     // Run skim
     let selected_items = Skim::run_with(&options, Some(items));
@@ -38,22 +76,348 @@ pub fn select_cpp_files(compile_commands: &[CompileCommand]) {
         Some(out) => {
             if out.is_abort {
                 eprintln!("Selection cancelled.");
-                return;
+                return Vec::new();
             }
             
-            // Output selected file paths
-            for item in out.selected_items {
-                println!("{}", item.output());
+            // Collect the compile commands of the selected files
+            let selected: Vec<CompileCommand> = out
+                .selected_items
+                .iter()
+                .filter_map(|item| {
+                    (**item)
+                        .as_any()
+                        .downcast_ref::<CompileCommandItem>()
+                        .map(|x| x.compile_command.clone())
+                })
+                .collect();
+
+            if let (Some(history), Some(path)) = (&mut history, history_file) {
+                let paths: Vec<String> = selected.iter().map(|x| x.source_path()).collect();
+                history.record(&paths, now);
+                history.save(path);
             }
+            selected
         }
         None => {
             eprintln!("No files selected.");
+            Vec::new()
         }
     }
 }
 
-/// Checks if a file has a C++ extension.
-fn is_cpp_file(filename: &str) -> bool {
-    let cpp_extensions = [".cpp", ".cxx", ".cc", ".c++", ".C"];
-    cpp_extensions.iter().any(|ext| filename.ends_with(ext))
+/// Matches the files offered by `select_cpp_files` against a query without a terminal.
+///
+/// The query uses the syntax and the fuzzy matching of the interactive picker, and the
+/// files are ranked the same way, so scripts see what a user typing the query would see.
+/// The selection history only orders equally ranked files and is not updated.
+///
+/// # Arguments
+///
+/// * `compile_commands` - The postprocessed compile commands.
+/// * `extensions` - The extensions of the offered files, without the dot.
+/// * `languages` - The languages of the offered files, used instead of the extensions if any.
+/// * `query` - The query, as typed in the picker.
+/// * `limit` - The most files returned, or `None` for all of them.
+/// * `history_file` - The selection history, or `None` to keep the order of the input.
+/// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+///
+/// # Returns
+///
+/// - `Vec<CompileCommand>` - The compile commands of the matching files, best match first.
+///
+/// # Examples
+///
+/// ```
+/// use ccj_postprocess::compile_commands::CompileCommand;
+/// use ccj_postprocess::skim_utility;
+///
+/// let entry = |file: &str| CompileCommand {
+///     command: format!("g++ -c {}", file),
+///     arguments: vec![],
+///     directory: "/work".to_string(),
+///     file: file.to_string(),
+///     output: "".to_string(),
+///     ..Default::default()
+/// };
+/// let compile_commands = vec![entry("parser/lexer.cc"), entry("main.cc"), entry("lex.cc"), entry("lex.c")];
+/// let extensions = vec!["cc".to_string()];
+/// let matched = skim_utility::filter_cpp_files(&compile_commands, &extensions, &[], "lex", Some(1), None, &[]);
+/// assert_eq!(matched.len(), 1);
+/// assert_eq!(matched[0].file, "lex.cc");
+/// ```
+pub fn filter_cpp_files(
+    compile_commands: &[CompileCommand],
+    extensions: &[String],
+    languages: &[String],
+    query: &str,
+    limit: Option<usize>,
+    history_file: Option<&Path>,
+    extra_wrappers: &[String],
+) -> Vec<CompileCommand> {
+    let history = history_file.map(SelectionHistory::load);
+    let cpp_files = offered_files(
+        compile_commands,
+        extensions,
+        languages,
+        history.as_ref(),
+        selection_history::now(),
+        extra_wrappers,
+    );
+
+    // the same engine as the picker, which also understands 'exact, ^prefix, suffix$ and !not
+    let engine = AndOrEngineFactory::new(ExactOrFuzzyEngineFactory::builder().build())
+        .create_engine(query);
+    let mut matched: Vec<(Rank, &CompileCommand)> = cpp_files
+        .into_iter()
+        // only the path is matched, so the entry itself is not copied into an item
+        .filter_map(|cc| {
+            engine
+                .match_item(Arc::new(cc.source_path()))
+                .map(|x| (x.rank, cc))
+        })
+        .collect();
+    // a stable sort, so equally ranked files stay in the offered order like in the picker
+    matched.sort_by_key(|(rank, _)| *rank);
+    matched
+        .into_iter()
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|(_, cc)| cc.clone())
+        .collect()
+}
+
+/// Lets the user pick one translation unit, with the compile command preview.
+///
+/// # Arguments
+///
+/// * `compile_commands` - The postprocessed compile commands.
+/// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+///
+/// # Returns
+///
+/// - `Option<CompileCommand>` - The picked compile command, or `None` if the pick is cancelled.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ccj_postprocess::compile_commands::CompileCommand;
+/// use ccj_postprocess::skim_utility;
+///
+/// let compile_commands = CompileCommand::parse("compile_commands.json");
+/// let picked = skim_utility::pick_one_file(&compile_commands, &[]);
+/// ```
+pub fn pick_one_file(
+    compile_commands: &[CompileCommand],
+    extra_wrappers: &[String],
+) -> Option<CompileCommand> {
+    let items = compile_command_items(compile_commands.iter().collect(), extra_wrappers);
+    let options = file_picker_options("Select a file: ", false);
+    let out = Skim::run_with(&options, Some(items)).filter(|x| !x.is_abort)?;
+    out.selected_items.first().and_then(|item| {
+        (**item)
+            .as_any()
+            .downcast_ref::<CompileCommandItem>()
+            .map(|x| x.compile_command.clone())
+    })
+}
+
+/// Lets the user pick some of the given strings.
+///
+/// With `multi`, the marked strings are returned, or the highlighted one if none is marked.
+///
+/// # Arguments
+///
+/// * `strings` - The strings to pick from.
+/// * `prompt` - The prompt of the picker.
+/// * `multi` - Whether several strings may be marked.
+///
+/// # Returns
+///
+/// - `Option<Vec<String>>` - The picked strings, or `None` if the pick is cancelled.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ccj_postprocess::skim_utility;
+///
+/// let flags = vec!["-O2".to_string(), "-g".to_string()];
+/// let picked = skim_utility::pick_strings(&flags, "Flags: ", true);
+/// ```
+pub fn pick_strings(strings: &[String], prompt: &str, multi: bool) -> Option<Vec<String>> {
+    let items = SkimItemReader::default().of_bufread(std::io::Cursor::new(strings.join("\n")));
+    let options = SkimOptionsBuilder::default()
+        .height("50%".to_string())
+        .multi(multi)
+        .prompt(prompt.to_string())
+        .build()
+        .unwrap();
+    let out = Skim::run_with(&options, Some(items)).filter(|x| !x.is_abort)?;
+    Some(
+        out.selected_items
+            .iter()
+            .map(|x| x.output().into_owned())
+            .collect(),
+    )
+}
+
+/// Lets the user type a line of text in a skim prompt.
+///
+/// # Arguments
+///
+/// * `prompt` - The prompt of the input line.
+///
+/// # Returns
+///
+/// - `Option<String>` - The typed text, or `None` if the input is cancelled.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ccj_postprocess::skim_utility;
+///
+/// let flags = skim_utility::read_line("Flags to insert: ");
+/// ```
+pub fn read_line(prompt: &str) -> Option<String> {
+    let (tx, items): (SkimItemSender, SkimItemReceiver) = unbounded();
+    drop(tx);
+    let options = SkimOptionsBuilder::default()
+        .height("3".to_string())
+        .prompt(prompt.to_string())
+        .build()
+        .unwrap();
+    let out = Skim::run_with(&options, Some(items)).filter(|x| !x.is_abort)?;
+    Some(out.query)
+}
+
+/// Returns the files offered for selection, the most frequently and recently picked first.
+fn offered_files<'a>(
+    compile_commands: &'a [CompileCommand],
+    extensions: &[String],
+    languages: &[String],
+    history: Option<&SelectionHistory>,
+    now: u64,
+    extra_wrappers: &[String],
+) -> Vec<&'a CompileCommand> {
+    let mut cpp_files: Vec<&CompileCommand> = compile_commands
+        .iter()
+        .filter(|cc| {
+            if languages.is_empty() {
+                has_extension(&cc.file, extensions)
+            } else {
+                cc.language(extra_wrappers).is_some_and(|x| languages.contains(&x))
+            }
+        })
+        .collect();
+    // skim keeps this order while the query is empty
+    if let Some(history) = history {
+        history.rank(&mut cpp_files, now);
+    }
+    cpp_files
+}
+
+/// Creates the skim items of the compile commands, each previewing its compile command.
+fn compile_command_items(
+    compile_commands: Vec<&CompileCommand>,
+    extra_wrappers: &[String],
+) -> SkimItemReceiver {
+    let (tx, items): (SkimItemSender, SkimItemReceiver) = unbounded();
+    for cc in compile_commands {
+        let item = CompileCommandItem {
+            compile_command: cc.clone(),
+            path: cc.source_path(),
+            preview: preview_text(cc, extra_wrappers),
+        };
+        let _ = tx.send(Arc::new(item));
+    }
+    items
+}
+
+/// Creates the skim options of a file picker with the compile command preview.
+fn file_picker_options(prompt: &str, multi: bool) -> SkimOptions {
+    SkimOptionsBuilder::default()
+        .height("50%".to_string())
+        .multi(multi)
+        .prompt(prompt.to_string())
+        // an empty command enables the preview window; each item provides its own text
+        .preview(Some(String::new()))
+        .preview_window("right:50%".to_string())
+        .bind(vec![format!("{}:toggle-preview", TOGGLE_PREVIEW_KEY)])
+        .header(Some(format!("{}: toggle preview", TOGGLE_PREVIEW_KEY)))
+        .build()
+        .unwrap()
+}
+
+/// Checks if a file has one of the extensions, given without the dot.
+fn has_extension(filename: &str, extensions: &[String]) -> bool {
+    extensions
+        .iter()
+        .any(|ext| filename.ends_with(&format!(".{}", ext)))
+}
+
+/// Formats a compile command one flag per line, with include directories and defines grouped.
+///
+/// An option and its separate value, such as `-D X`, share a line.
+///
+/// # Arguments
+///
+/// * `cc` - The postprocessed compile command.
+/// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+///
+/// # Examples
+///
+/// ```
+/// use ccj_postprocess::compile_commands::CompileCommand;
+/// use ccj_postprocess::skim_utility;
+///
+/// let cc = CompileCommand {
+///     command: "".to_string(),
+///     arguments: ["g++", "-O2", "-I/inc", "-isystem", "/sys", "-DX", "-D", "Y", "-c", "a.cc"].map(String::from).to_vec(),
+///     directory: "/work".to_string(),
+///     file: "a.cc".to_string(),
+///     output: "".to_string(),
+///     ..Default::default()
+/// };
+/// assert_eq!(
+///     skim_utility::preview_text(&cc, &[]),
+///     "/work/a.cc\ndirectory: /work\ncompiler: g++\n\n\
+///      include directories:\n  -I/inc\n  -isystem /sys\n\n\
+///      defines:\n  -DX\n  -D Y\n\n\
+///      other flags:\n  -O2\n"
+/// );
+/// ```
+pub fn preview_text(cc: &CompileCommand, extra_wrappers: &[String]) -> String {
+    let mut includes = Vec::new();
+    let mut defines = Vec::new();
+    let mut others = Vec::new();
+    let flags = cc.compile_flags(extra_wrappers);
+    for group in CompileCommand::option_groups(&flags) {
+        let option = group[0].as_str();
+        if INCLUDE_OPTIONS.iter().any(|x| option.starts_with(x)) {
+            includes.push(group.join(" "));
+        } else if DEFINE_OPTIONS.iter().any(|x| option.starts_with(x)) {
+            defines.push(group.join(" "));
+        } else {
+            others.push(group.join(" "));
+        }
+    }
+
+    let mut text = format!(
+        "{}\ndirectory: {}\ncompiler: {}\n",
+        cc.source_path(),
+        cc.directory,
+        cc.compiler(extra_wrappers)
+    );
+    for (title, group) in [
+        ("include directories", includes),
+        ("defines", defines),
+        ("other flags", others),
+    ] {
+        if group.is_empty() {
+            continue;
+        }
+        text.push_str(&format!("\n{}:\n", title));
+        for flag in group {
+            text.push_str(&format!("  {}\n", flag));
+        }
+    }
+    text
 }
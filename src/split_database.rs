@@ -0,0 +1,97 @@
+use crate::compile_commands::CompileCommand;
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+
+/// Splits compile commands into one database per subproject root.
+///
+/// Each rule is a directory prefix, relative to the current directory or absolute. A rule
+/// ending with `/*` makes every child directory of the prefix its own root. An entry goes to
+/// the longest root containing its source file; entries outside every root are returned as
+/// the catch-all database.
+///
+/// # Arguments
+///
+/// * `compile_commands` - The postprocessed compile commands.
+/// * `rules` - The directory prefix rules.
+///
+/// # Returns
+///
+/// - `(BTreeMap<String, Vec<CompileCommand>>, Vec<CompileCommand>)` - The entries of each
+///   root, and the entries outside every root.
+///
+/// # Examples
+///
+/// ```
+/// use ccj_postprocess::compile_commands::CompileCommand;
+/// use ccj_postprocess::split_database;
+///
+/// let entry = |file: &str| CompileCommand {
+///     command: format!("g++ -c {}", file),
+///     arguments: vec![],
+///     directory: "/work".to_string(),
+///     file: file.to_string(),
+///     output: "".to_string(),
+///     ..Default::default()
+/// };
+/// let compile_commands = vec![
+///     entry("zebu/sw_cc/kpar/a.cc"),
+///     entry("vcs-src/lib1/b.cc"),
+///     entry("vcs-src/lib2/sub/c.cc"),
+///     entry("top/d.cc"),
+/// ];
+/// let rules = ["/work/zebu/sw_cc/kpar".to_string(), "/work/vcs-src/*".to_string()];
+/// let (roots, rest) = split_database::split_by_prefix(compile_commands, &rules);
+/// assert_eq!(
+///     roots.keys().collect::<Vec<_>>(),
+///     vec!["/work/vcs-src/lib1", "/work/vcs-src/lib2", "/work/zebu/sw_cc/kpar"]
+/// );
+/// assert_eq!(rest[0].file, "top/d.cc");
+/// ```
+pub fn split_by_prefix(
+    compile_commands: Vec<CompileCommand>,
+    rules: &[String],
+) -> (BTreeMap<String, Vec<CompileCommand>>, Vec<CompileCommand>) {
+    let rules = rules
+        .iter()
+        .map(|x| {
+            let (prefix, per_child) = match x.strip_suffix("/*") {
+                Some(prefix) => (prefix, true),
+                None => (x.as_str(), false),
+            };
+            let prefix = std::path::absolute(prefix)
+                .unwrap_or_else(|_| panic!("[Error] invalid split rule {}", x));
+            (CompileCommand::normalize_path(&prefix), per_child)
+        })
+        .collect::<Vec<_>>();
+
+    let mut roots: BTreeMap<String, Vec<CompileCommand>> = BTreeMap::new();
+    let mut rest = Vec::new();
+    for cc in compile_commands {
+        let source_path = cc.source_path();
+        let root = rules
+            .iter()
+            .filter_map(|(prefix, per_child)| subproject_root(&source_path, prefix, *per_child))
+            .max_by_key(|x| x.len());
+        match root {
+            Some(root) => roots.entry(root).or_default().push(cc),
+            None => rest.push(cc),
+        }
+    }
+    (roots, rest)
+}
+
+/// Returns the root of a source file under a rule, or `None` if the rule does not contain it.
+fn subproject_root(source_path: &str, prefix: &str, per_child: bool) -> Option<String> {
+    let relative = Path::new(source_path).strip_prefix(prefix).ok()?;
+    if !per_child {
+        return Some(prefix.to_string());
+    }
+    let mut components = relative.components();
+    // the file itself is not a child directory
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(child)), Some(_)) => {
+            Some(Path::new(prefix).join(child).to_string_lossy().into_owned())
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,167 @@
+use crate::compile_commands::CompileCommand;
+use std::path::Path;
+
+/// Options naming an include directory.
+const INCLUDE_OPTIONS: [&str; 3] = ["-I", "-isystem", "-iquote"];
+
+/// The column names of the report.
+const COLUMNS: [&str; 8] = [
+    "path",
+    "compiler",
+    "standard",
+    "optimization",
+    "include_dirs",
+    "defines",
+    "exists",
+    "source",
+];
+
+/// The attributes of one translation unit.
+#[derive(Debug)]
+pub struct UnitReport {
+    /// The normalized source path.
+    pub path: String,
+    /// The compiler, past any compiler launcher.
+    pub compiler: String,
+    /// The value of the last `-std=` option, or empty without one.
+    pub standard: String,
+    /// The last `-O` option, or empty without one.
+    pub optimization: String,
+    /// The number of `-I`, `-isystem` and `-iquote` directories.
+    pub include_dirs: usize,
+    /// The number of `-D` macros.
+    pub defines: usize,
+    /// Whether the source file exists on the filesystem.
+    pub exists: bool,
+    /// The compile database the entry was read from.
+    pub source: String,
+}
+
+impl UnitReport {
+    /// Collects the attributes of every translation unit.
+    ///
+    /// # Arguments
+    ///
+    /// * `compile_commands` - The postprocessed compile commands.
+    /// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    /// use ccj_postprocess::unit_report::UnitReport;
+    ///
+    /// let cc = CompileCommand {
+    ///     command: "".to_string(),
+    ///     arguments: ["ccache", "g++", "-std=c++17", "-O2", "-I/inc", "-isystem", "/sys", "-DA", "-c", "a.cc"]
+    ///         .map(String::from)
+    ///         .to_vec(),
+    ///     directory: "/work".to_string(),
+    ///     file: "a.cc".to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let reports = UnitReport::collect(&[cc], &[]);
+    /// assert_eq!(reports[0].compiler, "g++");
+    /// assert_eq!(reports[0].standard, "c++17");
+    /// assert_eq!(reports[0].optimization, "-O2");
+    /// assert_eq!(reports[0].include_dirs, 2);
+    /// assert_eq!(reports[0].defines, 1);
+    /// ```
+    pub fn collect(
+        compile_commands: &[CompileCommand],
+        extra_wrappers: &[String],
+    ) -> Vec<UnitReport> {
+        compile_commands
+            .iter()
+            .map(|cc| {
+                let flags = cc.compile_flags(extra_wrappers);
+                let path = cc.source_path();
+                UnitReport {
+                    exists: Path::new(&path).exists(),
+                    path,
+                    compiler: cc.compiler(extra_wrappers).to_string(),
+                    standard: flags
+                        .iter()
+                        .rev()
+                        .find_map(|x| x.strip_prefix("-std="))
+                        .unwrap_or_default()
+                        .to_string(),
+                    optimization: flags
+                        .iter()
+                        .rev()
+                        .find(|x| x.starts_with("-O"))
+                        .cloned()
+                        .unwrap_or_default(),
+                    include_dirs: flags
+                        .iter()
+                        .filter(|x| INCLUDE_OPTIONS.iter().any(|o| x.starts_with(o)))
+                        .count(),
+                    defines: flags.iter().filter(|x| x.starts_with("-D")).count(),
+                    source: cc.source.path.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Formats the reports as a table with a header row.
+    ///
+    /// With a comma delimiter, fields are quoted as in CSV where needed; with any other
+    /// delimiter, the delimiter and line breaks in fields are replaced by spaces.
+    ///
+    /// # Arguments
+    ///
+    /// * `reports` - The unit reports.
+    /// * `delimiter` - The field delimiter, `,` for CSV or `\t` for TSV.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::unit_report::UnitReport;
+    ///
+    /// let report = UnitReport {
+    ///     path: "/work/a b.cc".to_string(),
+    ///     compiler: "g++".to_string(),
+    ///     standard: "c++17".to_string(),
+    ///     optimization: "-O2".to_string(),
+    ///     include_dirs: 2,
+    ///     defines: 1,
+    ///     exists: false,
+    ///     source: "x,y.json".to_string(),
+    /// };
+    /// assert_eq!(
+    ///     UnitReport::to_table(&[report], ','),
+    ///     "path,compiler,standard,optimization,include_dirs,defines,exists,source\n\
+    ///      /work/a b.cc,g++,c++17,-O2,2,1,false,\"x,y.json\"\n"
+    /// );
+    /// ```
+    pub fn to_table(reports: &[UnitReport], delimiter: char) -> String {
+        let field = |x: &str| {
+            if delimiter != ',' {
+                x.replace([delimiter, '\n', '\r'], " ")
+            } else if x.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", x.replace('"', "\"\""))
+            } else {
+                x.to_string()
+            }
+        };
+        let separator = delimiter.to_string();
+        let mut table = COLUMNS.join(&separator);
+        table.push('\n');
+        for report in reports {
+            let row = [
+                field(&report.path),
+                field(&report.compiler),
+                field(&report.standard),
+                field(&report.optimization),
+                report.include_dirs.to_string(),
+                report.defines.to_string(),
+                report.exists.to_string(),
+                field(&report.source),
+            ];
+            table.push_str(&row.join(&separator));
+            table.push('\n');
+        }
+        table
+    }
+}
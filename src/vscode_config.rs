@@ -0,0 +1,238 @@
+use crate::compile_commands::CompileCommand;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Options naming an include directory.
+const INCLUDE_OPTIONS: [&str; 3] = ["-I", "-isystem", "-iquote"];
+
+/// The version of the `c_cpp_properties.json` schema.
+const PROPERTIES_VERSION: u32 = 4;
+
+/// The sorted include directories and defines identifying a configuration.
+type IncludesAndDefines = (Vec<String>, Vec<String>);
+
+/// The content of a `c_cpp_properties.json` file.
+#[derive(Serialize, Debug)]
+pub struct CppProperties {
+    pub configurations: Vec<Configuration>,
+    pub version: u32,
+}
+
+/// One configuration of the Microsoft C/C++ extension.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Configuration {
+    /// The common directory of the translation units of the configuration.
+    pub name: String,
+    pub include_path: Vec<String>,
+    pub defines: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub c_standard: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpp_standard: Option<String>,
+    pub compiler_path: String,
+    pub intelli_sense_mode: String,
+}
+
+impl CppProperties {
+    /// Groups the translation units by identical include and define sets.
+    ///
+    /// Every group becomes one configuration named after the common directory of its
+    /// translation units. The standard and the compiler come from the first unit of the
+    /// group, and the IntelliSense mode is inferred from the compiler.
+    ///
+    /// # Arguments
+    ///
+    /// * `compile_commands` - The postprocessed compile commands.
+    /// * `extra_wrappers` - Compiler wrappers to skip in addition to the known ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ccj_postprocess::compile_commands::CompileCommand;
+    /// use ccj_postprocess::vscode_config::CppProperties;
+    ///
+    /// let entry = |file: &str, arguments: &[&str]| CompileCommand {
+    ///     command: "".to_string(),
+    ///     arguments: arguments.iter().map(|x| x.to_string()).collect(),
+    ///     directory: "/work".to_string(),
+    ///     file: file.to_string(),
+    ///     output: "".to_string(),
+    ///     ..Default::default()
+    /// };
+    /// let compile_commands = vec![
+    ///     entry("src/a.cc", &["/usr/bin/clang++", "-std=c++17", "-Iinc", "-DA", "-c", "src/a.cc"]),
+    ///     entry("src/b.cc", &["/usr/bin/clang++", "-std=c++17", "-DA", "-Iinc", "-c", "src/b.cc"]),
+    ///     entry("lib/c.c", &["gcc", "-std=gnu11", "-c", "lib/c.c"]),
+    /// ];
+    /// let properties = CppProperties::collect(&compile_commands, &[]);
+    /// assert_eq!(properties.configurations.len(), 2);
+    /// assert_eq!(properties.configurations[0].name, "/work/src");
+    /// assert_eq!(properties.configurations[0].include_path, vec!["/work/inc"]);
+    /// assert_eq!(properties.configurations[0].defines, vec!["A"]);
+    /// assert_eq!(properties.configurations[0].cpp_standard.as_deref(), Some("c++17"));
+    /// assert_eq!(properties.configurations[0].intelli_sense_mode, "linux-clang-x64");
+    /// assert_eq!(properties.configurations[1].c_standard.as_deref(), Some("gnu11"));
+    /// assert_eq!(properties.configurations[1].intelli_sense_mode, "linux-gcc-x64");
+    /// ```
+    pub fn collect(
+        compile_commands: &[CompileCommand],
+        extra_wrappers: &[String],
+    ) -> CppProperties {
+        // groups in the order of their first translation unit
+        let mut groups: Vec<Vec<&CompileCommand>> = Vec::new();
+        let mut position = HashMap::<IncludesAndDefines, usize>::new();
+        for cc in compile_commands {
+            let flags = cc.compile_flags(extra_wrappers);
+            let mut key = (include_directories(cc, &flags), defines(&flags));
+            key.0.sort();
+            key.1.sort();
+            match position.get(&key) {
+                Some(&index) => groups[index].push(cc),
+                None => {
+                    position.insert(key, groups.len());
+                    groups.push(vec![cc]);
+                }
+            }
+        }
+
+        let mut configurations = Vec::new();
+        let mut name_counts = HashMap::<String, usize>::new();
+        for members in groups {
+            let first = members[0];
+            let flags = first.compile_flags(extra_wrappers);
+            let standard = flags
+                .iter()
+                .rev()
+                .find_map(|x| x.strip_prefix("-std="))
+                .map(|x| x.to_string());
+            let is_c = standard
+                .as_deref()
+                .map(|x| !x.contains("++"))
+                .unwrap_or_else(|| first.file.ends_with(".c"));
+            let compiler = first.compiler(extra_wrappers).to_string();
+
+            let mut name = common_directory(&members);
+            let name_count = name_counts.entry(name.clone()).or_insert(0);
+            *name_count += 1;
+            if *name_count > 1 {
+                name = format!("{} ({})", name, name_count);
+            }
+            configurations.push(Configuration {
+                name,
+                include_path: include_directories(first, &flags),
+                defines: defines(&flags),
+                c_standard: standard.clone().filter(|_| is_c),
+                cpp_standard: standard.filter(|_| !is_c),
+                intelli_sense_mode: intelli_sense_mode(&compiler).to_string(),
+                compiler_path: compiler,
+            });
+        }
+        CppProperties {
+            configurations,
+            version: PROPERTIES_VERSION,
+        }
+    }
+
+    /// Writes the configurations as a `c_cpp_properties.json` file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the written file.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ccj_postprocess::vscode_config::CppProperties;
+    ///
+    /// let properties = CppProperties::collect(&[], &[]);
+    /// properties.write(".vscode/c_cpp_properties.json");
+    /// ```
+    pub fn write(&self, path: &str) {
+        let mut context = serde_json::to_string_pretty(self).unwrap();
+        context.push('\n');
+        std::fs::write(path, context).unwrap_or_else(|_| panic!("cannot write the file {}", path));
+    }
+}
+
+/// Returns the absolute include directories of a compile command, in command-line order.
+fn include_directories(cc: &CompileCommand, flags: &[String]) -> Vec<String> {
+    let mut directories = Vec::new();
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        let Some(option) = INCLUDE_OPTIONS.iter().find(|x| flag.starts_with(*x)) else {
+            continue;
+        };
+        let directory = match &flag[option.len()..] {
+            "" => flags.next().map(|x| x.as_str()),
+            directory => Some(directory),
+        };
+        if let Some(directory) = directory {
+            let directory =
+                CompileCommand::normalize_path(&Path::new(&cc.directory).join(directory));
+            if !directories.contains(&directory) {
+                directories.push(directory);
+            }
+        }
+    }
+    directories
+}
+
+/// Returns the `-D` macros of the flags, in command-line order.
+fn defines(flags: &[String]) -> Vec<String> {
+    let mut defines = Vec::new();
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        let define = match flag.strip_prefix("-D") {
+            Some("") => flags.next().cloned(),
+            Some(define) => Some(define.to_string()),
+            None => None,
+        };
+        if let Some(define) = define &&
+            !defines.contains(&define)
+        {
+            defines.push(define);
+        }
+    }
+    defines
+}
+
+/// Returns the deepest directory containing the source files of every compile command.
+fn common_directory(compile_commands: &[&CompileCommand]) -> String {
+    let mut common = Path::new(&compile_commands[0].source_path())
+        .parent()
+        .map(|x| x.to_path_buf())
+        .unwrap_or_default();
+    for cc in &compile_commands[1..] {
+        let source_path = cc.source_path();
+        while !Path::new(&source_path).starts_with(&common) {
+            if !common.pop() {
+                break;
+            }
+        }
+    }
+    common.to_string_lossy().into_owned()
+}
+
+/// Infers the IntelliSense mode from the name of the compiler.
+fn intelli_sense_mode(compiler: &str) -> &'static str {
+    let name = Path::new(compiler)
+        .file_name()
+        .and_then(|x| x.to_str())
+        .unwrap_or(compiler);
+    let is_arm64 = name.contains("aarch64") || name.contains("arm64");
+    if name.contains("clang") {
+        if is_arm64 {
+            "linux-clang-arm64"
+        } else {
+            "linux-clang-x64"
+        }
+    } else if name == "cl" || name == "cl.exe" {
+        "windows-msvc-x64"
+    } else if is_arm64 {
+        "linux-gcc-arm64"
+    } else {
+        "linux-gcc-x64"
+    }
+}